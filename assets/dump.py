@@ -87,14 +87,16 @@ def parse_class(file: str):
 
 def main():
     cls = parse_class("./classes/Node.xml")
-    files = []
-    for _, _, filenames in walk("./classes/"):
-        files.extend(filenames)
-        break
     output = {}
-    for file in files:
-        cls = parse_class(f"./classes/{file}")
-        output[cls["name"]] = cls
+    # @GDScript functions like `assert` and `len` are documented in the gdscript module
+    for directory in ["./classes/", "./modules/gdscript/doc_classes/"]:
+        files = []
+        for _, _, filenames in walk(directory):
+            files.extend(filenames)
+            break
+        for file in files:
+            cls = parse_class(f"{directory}{file}")
+            output[cls["name"]] = cls
 
     with open("type_info.json", "w+") as f:
         json.dump(output, f, indent=2, sort_keys=True)
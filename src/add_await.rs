@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{
+    filedb::FileDatabase,
+    utils::{node_content, point_to_position, position_to_point, uri_to_path},
+};
+
+/// Offers to await a call to a coroutine of the script whose result is used as a value,
+/// without `await` the value is the coroutine state instead of the returned value
+pub fn add_await_action(
+    params: &CodeActionParams,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+    let root = source_file.tree.root_node();
+
+    let point = position_to_point(params.range.start);
+    let mut call = root.descendant_for_point_range(point, point)?;
+    while call.kind() != "call" {
+        call = call.parent()?;
+    }
+    let name = node_content(&call.child(0)?, &file);
+    let parent = call.parent()?;
+    // a call statement on its own discards the value, awaiting it is up to the author
+    if matches!(parent.kind(), "await_expression" | "expression_statement") {
+        return None;
+    }
+    if !is_coroutine(root, name, &file) {
+        return None;
+    }
+
+    let position = point_to_position(call.start_position());
+    let edit = TextEdit::new(Range::new(position, position), "await ".to_string());
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Add `await` to `{name}` call"),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Checks if the script function awaits something in its body, lambdas are their own coroutines
+fn is_coroutine(root: Node, name: &str, file: &str) -> bool {
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|child| child.kind() == "function_definition")
+        .filter(|function| {
+            function
+                .child_by_field_name("name")
+                .is_some_and(|function_name| node_content(&function_name, file) == name)
+        })
+        .filter_map(|function| function.child_by_field_name("body"))
+        .any(contains_await)
+}
+
+fn contains_await(node: Node) -> bool {
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .any(|child| match child.kind() {
+            "await_expression" => true,
+            "lambda" => false,
+            _ => contains_await(child),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{apply_action, code_action_params, test_filedb};
+
+    use super::add_await_action;
+
+    fn add_await(file: &str, position: Position) -> Option<String> {
+        let params = code_action_params(Range::new(position, position));
+        let action = add_await_action(&params, &test_filedb(file));
+        apply_action(file, action.as_ref())
+    }
+
+    #[test]
+    fn coroutine_call_used_as_value_is_awaited() {
+        let file = "func load_level():
+\tawait get_tree().process_frame
+\treturn 1
+
+func sync_level():
+\treturn 2
+
+func foo():
+\tvar level = load_level()
+\tvar other = sync_level()";
+        assert_eq!(
+            add_await(file, Position::new(8, 14)).unwrap(),
+            "func load_level():
+\tawait get_tree().process_frame
+\treturn 1
+
+func sync_level():
+\treturn 2
+
+func foo():
+\tvar level = await load_level()
+\tvar other = sync_level()"
+        );
+        assert_eq!(add_await(file, Position::new(9, 14)), None);
+    }
+}
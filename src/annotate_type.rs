@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    filedb::FileDatabase,
+    symbol_table::SymbolTable,
+    typedb::{SymbolType, TypeDatabase},
+    utils::{point_to_position, position_to_point, uri_to_path},
+};
+
+/// Offers to replace `var x = value` or `var x := value` with `var x: T = value`
+/// using the type inferred from the value
+pub fn annotate_type_action(
+    params: &CodeActionParams,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let point = position_to_point(params.range.start);
+    let mut statement = tree.root_node().descendant_for_point_range(point, point)?;
+    while !matches!(statement.kind(), "variable_statement" | "const_statement") {
+        statement = statement.parent()?;
+    }
+    let name_node = statement.child_by_field_name("name")?;
+    let value_node = statement.child_by_field_name("value")?;
+
+    let mut st = SymbolTable::new(typedb);
+    st.build_table(tree, &file);
+    let symbol = st.declared_symbol(name_node, &file)?;
+    if symbol.static_typed {
+        return None;
+    }
+    let ttype = symbol.ttype.as_ref()?;
+    if ttype == &SymbolType::Object("Variant".to_string()) {
+        return None;
+    }
+
+    let edit = TextEdit::new(
+        Range::new(
+            point_to_position(name_node.end_position()),
+            point_to_position(value_node.start_position()),
+        ),
+        format!(": {} = ", ttype.to_string()),
+    );
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Add type annotation `{}`", ttype.to_string()),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::{
+        symbol_table::SymbolTable,
+        test_utils::{TEST_TYPEDB, apply_action, code_action_params, test_filedb},
+        typedb::{SymbolType, VariantType},
+        utils::parse_file,
+    };
+
+    use super::annotate_type_action;
+
+    fn annotate(file: &str, position: Position) -> Option<String> {
+        let params = code_action_params(Range::new(position, position));
+        let action = annotate_type_action(&params, &TEST_TYPEDB, &test_filedb(file));
+        apply_action(file, action.as_ref())
+    }
+
+    #[test]
+    fn inferred_typed_array_is_annotated() {
+        let file = "func foo():
+\tvar positions := [Vector3.ZERO, Vector3.ONE]";
+        let tree = parse_file(file).unwrap();
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        st.build_table(&tree, file);
+        let name_node = tree
+            .root_node()
+            .descendant_for_point_range(
+                tree_sitter::Point::new(1, 5),
+                tree_sitter::Point::new(1, 5),
+            )
+            .unwrap();
+        let symbol = st.declared_symbol(name_node, file).unwrap();
+        assert_eq!(symbol.ttype, Some(SymbolType::Array(VariantType::Vector3)));
+
+        assert_eq!(
+            annotate(file, Position::new(1, 6)).unwrap(),
+            "func foo():
+\tvar positions: Array[Vector3] = [Vector3.ZERO, Vector3.ONE]"
+        );
+    }
+
+    #[test]
+    fn annotated_variable_has_no_action() {
+        let file = "func foo():
+\tvar count: int = 10";
+        assert_eq!(annotate(file, Position::new(1, 6)), None);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Flag checked by long running analysis to stop early when its result is no longer needed
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<TokenState>);
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    /// Cancelling the parent cancels all of its children
+    parent: Option<CancellationToken>,
+}
+
+impl CancellationToken {
+    /// Returns token cancelled either by itself or together with this token,
+    /// e.g. a request is cancelled by the client or by the server shutdown
+    pub fn child_token(&self) -> Self {
+        Self(Arc::new(TokenState {
+            cancelled: AtomicBool::new(false),
+            parent: Some(self.clone()),
+        }))
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+            || self
+                .0
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_cancelled())
+    }
+
+    /// Returns guard that cancels the token when dropped, `ConcurrencyLayer` drops
+    /// the future of a request cancelled by the client, so the guard is moved into it
+    pub fn drop_guard(&self) -> DropGuard {
+        DropGuard(self.clone())
+    }
+}
+
+pub struct DropGuard(CancellationToken);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CancellationToken;
+
+    #[test]
+    fn dropped_guard_cancels_token() {
+        let token = CancellationToken::default();
+        let guard = token.drop_guard();
+        assert!(!token.is_cancelled());
+        drop(guard);
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn parent_cancels_children_only() {
+        let parent = CancellationToken::default();
+        let first = parent.child_token();
+        let second = parent.child_token();
+        first.cancel();
+        assert!(!parent.is_cancelled());
+        assert!(!second.is_cancelled());
+        parent.cancel();
+        assert!(second.is_cancelled());
+    }
+}
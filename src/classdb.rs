@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_lsp::lsp_types::Range;
+use parking_lot::RwLock;
+use tree_sitter::Tree;
+
+use crate::{
+    filedb::FileDatabase,
+    symbol_table::extends_type,
+    typedb::SymbolType,
+    utils::{node_content, node_to_range, parse_file},
+};
+
+/// Registry of the `class_name` declarations found in workspace scripts.
+#[derive(Default, Clone)]
+pub struct ClassDatabase {
+    pub(crate) classes: Arc<RwLock<HashMap<String, ScriptClass>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScriptClass {
+    pub path: String,
+    pub parent: Option<SymbolType>,
+    /// Range of the name in the `class_name` statement
+    pub range: Range,
+}
+
+impl ClassDatabase {
+    /// Reads `class_name` declarations of all workspace scripts, returns number of scripts
+    pub fn scan_workspace(&self, root: &Path) -> usize {
+        let scripts = workspace_scripts(root);
+        for path in &scripts {
+            self.read_from_disk(path);
+        }
+        scripts.len()
+    }
+
+    /// Re-reads the `class_name` declaration of a script that isn't opened in the editor
+    pub fn read_from_disk(&self, path: &Path) {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return;
+        };
+        let Some(tree) = parse_file(&content) else {
+            return;
+        };
+        self.update_class(&path.to_string_lossy(), &tree, &content);
+    }
+
+    pub fn file_removed(&self, file_path: &str) {
+        self.classes
+            .write()
+            .retain(|_, class| class.path != file_path);
+    }
+
+    /// Re-reads the `class_name` declaration of an opened file
+    pub fn file_updated(&self, file_path: &str, filedb: &FileDatabase) {
+        let lock = filedb.files.read();
+        let Some(source_file) = lock.get(file_path) else {
+            return;
+        };
+        let file = source_file.content.to_string();
+        self.update_class(file_path, &source_file.tree, &file);
+    }
+
+    pub fn update_class(&self, file_path: &str, tree: &Tree, file: &str) {
+        let mut lock = self.classes.write();
+        lock.retain(|_, class| class.path != file_path);
+        if let Some((name, class)) = collect_script_class(file_path, tree, file) {
+            lock.insert(name, class);
+        }
+    }
+}
+
+/// Returns paths of all `.gd` files in the workspace, hidden directories are skipped
+pub fn workspace_scripts(root: &Path) -> Vec<PathBuf> {
+    let mut scripts = Vec::new();
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return scripts;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if path.is_dir() {
+            if !is_hidden {
+                scripts.extend(workspace_scripts(&path));
+            }
+        } else if path.extension().is_some_and(|ext| ext == "gd") {
+            scripts.push(path);
+        }
+    }
+    scripts
+}
+
+pub fn collect_script_class(
+    file_path: &str,
+    tree: &Tree,
+    file: &str,
+) -> Option<(String, ScriptClass)> {
+    let root = tree.root_node();
+    let mut name = None;
+    let mut parent = None;
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        match child.kind() {
+            "class_name_statement" => {
+                let name_node = child.child_by_field_name("name").or(child.child(1))?;
+                name = Some((
+                    node_content(&name_node, file).to_string(),
+                    node_to_range(&name_node),
+                ));
+                parent = parent.or_else(|| extends_type(child, file));
+            }
+            "extends_statement" => {
+                parent = extends_type(child, file).or(parent);
+            }
+            _ => (),
+        }
+    }
+    let (name, range) = name?;
+    Some((
+        name,
+        ScriptClass {
+            path: file_path.to_string(),
+            parent,
+            range,
+        },
+    ))
+}
@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+
+use async_lsp::lsp_types::{
+    ApplyWorkspaceEditParams, ApplyWorkspaceEditResponse, InsertTextFormat, Range, Url,
+    WorkspaceEdit, request::Request,
+};
+use serde_json::Value;
+
+use crate::{
+    config::Config, extract_into_function::extract_into_function_edit, filedb::FileDatabase,
+    utils::uri_to_path,
+};
+
+pub const TOGGLE_INLAY_HINTS: &str = "gdscript.toggleInlayHints";
+pub const EXTRACT_FUNCTION: &str = "gdscript.extractFunction";
+
+/// `workspace/applyEdit` whose text edits are snippets,
+/// sent to clients with the `experimental.snippetTextEdit` extension
+pub enum ApplySnippetWorkspaceEdit {}
+
+impl Request for ApplySnippetWorkspaceEdit {
+    type Params = Value;
+    type Result = ApplyWorkspaceEditResponse;
+    const METHOD: &'static str = "workspace/applyEdit";
+}
+
+pub fn supported_commands() -> Vec<String> {
+    vec![TOGGLE_INLAY_HINTS.to_string(), EXTRACT_FUNCTION.to_string()]
+}
+
+/// Extracts statements of a file into a function, arguments are the uri of the file,
+/// the selected range and an optional name overriding the configured one
+pub fn extract_function(
+    config: &Config,
+    filedb: &FileDatabase,
+    snippets: bool,
+    arguments: &[Value],
+) -> Option<WorkspaceEdit> {
+    let uri: Url = serde_json::from_value(arguments.first()?.clone()).ok()?;
+    let range: Range = serde_json::from_value(arguments.get(1)?.clone()).ok()?;
+    let mut config = config.clone();
+    if let Some(name) = arguments.get(2).and_then(Value::as_str) {
+        config.extract_function_name = name.to_string();
+    }
+    extract_into_function_edit(&uri, range, &config, filedb, snippets)
+}
+
+/// Marks every text edit of the params as a snippet for [`ApplySnippetWorkspaceEdit`]
+pub fn snippet_edit_params(params: ApplyWorkspaceEditParams) -> Value {
+    let mut params = serde_json::to_value(params).unwrap_or_default();
+    let document_changes = params
+        .pointer_mut("/edit/documentChanges")
+        .and_then(Value::as_array_mut);
+    for change in document_changes.into_iter().flatten() {
+        let edits = change.get_mut("edits").and_then(Value::as_array_mut);
+        for edit in edits.into_iter().flatten() {
+            edit["insertTextFormat"] = serde_json::json!(InsertTextFormat::SNIPPET);
+        }
+    }
+    params
+}
+
+/// Flips inlay hints visibility for a file whose uri is passed as the first argument,
+/// returns whether hints are now shown for the file
+pub fn toggle_inlay_hints(
+    disabled_files: &mut HashSet<String>,
+    arguments: &[Value],
+) -> Option<bool> {
+    let uri: Url = serde_json::from_value(arguments.first()?.clone()).ok()?;
+    let file_path = uri_to_path(&uri);
+    if disabled_files.remove(&file_path) {
+        Some(true)
+    } else {
+        disabled_files.insert(file_path);
+        Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use async_lsp::lsp_types::{ApplyWorkspaceEditParams, DocumentChanges, OneOf, Url};
+    use serde_json::json;
+
+    use crate::{config::Config, filedb::FileDatabase};
+
+    use super::{extract_function, snippet_edit_params, toggle_inlay_hints};
+
+    #[test]
+    fn toggle_inlay_hints_for_file() {
+        let mut disabled_files = HashSet::new();
+        let arguments = [json!("file:///project/main.gd")];
+
+        assert_eq!(
+            toggle_inlay_hints(&mut disabled_files, &arguments),
+            Some(false)
+        );
+        assert!(disabled_files.contains("/project/main.gd"));
+
+        assert_eq!(
+            toggle_inlay_hints(&mut disabled_files, &arguments),
+            Some(true)
+        );
+        assert!(disabled_files.is_empty());
+
+        assert_eq!(toggle_inlay_hints(&mut disabled_files, &[]), None);
+    }
+
+    #[test]
+    fn extract_function_with_given_name() {
+        let file = "func foo():
+\tvar a = 10
+\tprint(a)
+\treturn a
+";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let uri = Url::from_file_path("/test.gd").unwrap();
+        let arguments = [
+            json!(uri),
+            json!({
+                "start": { "line": 2, "character": 1 },
+                "end": { "line": 2, "character": 9 }
+            }),
+            json!("show"),
+        ];
+        let edit = extract_function(&Config::default(), &filedb, false, &arguments);
+
+        let Some(DocumentChanges::Edits(document_edits)) = edit.unwrap().document_changes else {
+            panic!("expected document edits");
+        };
+        assert_eq!(document_edits[0].text_document.uri, uri);
+        let new_texts = document_edits[0]
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit.new_text.as_str(),
+                OneOf::Right(edit) => edit.text_edit.new_text.as_str(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(new_texts, ["show(a)", "\n\n\nfunc show(a):\n\tprint(a)"]);
+
+        assert_eq!(
+            extract_function(&Config::default(), &filedb, false, &arguments[..1]),
+            None
+        );
+    }
+
+    #[test]
+    fn extract_function_as_snippet() {
+        let file = "func foo():
+\tprint($Label.text)
+\treturn 1
+";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let arguments = [
+            json!("file:///test.gd"),
+            json!({
+                "start": { "line": 1, "character": 1 },
+                "end": { "line": 1, "character": 19 }
+            }),
+        ];
+        let edit = extract_function(&Config::default(), &filedb, true, &arguments).unwrap();
+        let params = snippet_edit_params(ApplyWorkspaceEditParams { label: None, edit });
+        let edits = params["edit"]["documentChanges"][0]["edits"]
+            .as_array()
+            .unwrap();
+        assert_eq!(edits[0]["newText"], "fun_name()");
+        assert_eq!(
+            edits[1]["newText"],
+            "\n\n\nfunc ${0:fun_name}():\n\tprint(\\$Label.text)"
+        );
+        assert!(edits.iter().all(|edit| edit["insertTextFormat"] == 2));
+    }
+}
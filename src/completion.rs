@@ -0,0 +1,572 @@
+use std::collections::HashSet;
+
+use async_lsp::lsp_types::*;
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use crate::{
+    cancellation::CancellationToken,
+    filedb::FileDatabase,
+    scenedb::SceneDatabase,
+    symbol_table::SymbolTable,
+    typedb::{GLOBAL_SCOPES, SymbolType, TypeDatabase},
+    utils::{
+        doc_comment, function_header, markdown_to_plain_text, node_content, position_to_byte,
+        walk_tree,
+    },
+};
+
+/// Identity of a class member stored in `CompletionItem::data`,
+/// details of the member are filled in only when the item is resolved
+#[derive(Serialize, Deserialize)]
+struct CompletionData {
+    class: String,
+    name: String,
+}
+
+pub fn make_completions(
+    path: &str,
+    position: Position,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    scenedb: &SceneDatabase,
+    cancellation: &CancellationToken,
+) -> Vec<CompletionItem> {
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let offset = position_to_byte(&file, position);
+    let prefix_start = file[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(offset, |(i, _)| i);
+    // blank lines after the last statement of a block are not a part of its body node,
+    // so the scope is taken from the last non-whitespace character before the cursor
+    let scope_byte = file[..prefix_start].trim_end().len().saturating_sub(1);
+
+    // completion is requested on every keystroke, only the edited function
+    // and the class level declarations are needed for it
+    let mut st = SymbolTable::new(typedb);
+    st.set_cancellation(cancellation.clone());
+    st.build_table_in_ranges(tree, &file, vec![scope_byte..offset]);
+    if cancellation.is_cancelled() {
+        return Vec::new();
+    }
+
+    if let Some(node_path) = node_path_before(&file[..offset]) {
+        let parent_path = node_path.rsplit_once('/').map_or("", |(parent, _)| parent);
+        return scenedb
+            .child_nodes(path, parent_path)
+            .into_iter()
+            .map(|node| CompletionItem {
+                label: node.name().to_string(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: node.ttype,
+                ..Default::default()
+            })
+            .collect();
+    }
+    let root = tree.root_node();
+    let scope_node = root
+        .descendant_for_byte_range(scope_byte, scope_byte)
+        .unwrap_or(root);
+    let scope_id = st.scope_of(scope_node);
+
+    let mut items = Vec::new();
+    if prefix_start > 0 && file.as_bytes()[prefix_start - 1] == b'.' {
+        let Some(receiver) = receiver_node(root, prefix_start - 1) else {
+            return items;
+        };
+        let receiver_type = st.infer_type(scope_id, receiver, &file).or_else(|| {
+            let ttype = SymbolType::from_str(node_content(&receiver, &file));
+            typedb.classes.contains_key(&ttype).then_some(ttype)
+        });
+        if let Some(receiver_type) = receiver_type {
+            add_class_members(&mut items, typedb, &receiver_type);
+        }
+        return items;
+    }
+
+    let line_start = file[..prefix_start].rfind('\n').map_or(0, |i| i + 1);
+    if let Some(target_type) =
+        assignment_target_type(&file[line_start..prefix_start], &st, scope_id, prefix_start)
+    {
+        for member in enum_members(root, &file, &target_type) {
+            items.push(CompletionItem {
+                label: format!("{target_type}.{member}"),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(target_type.clone()),
+                ..Default::default()
+            });
+        }
+    }
+
+    for symbol in st.visible_symbols(scope_id, prefix_start) {
+        let ttype = symbol.ttype.as_ref().map(|ttype| ttype.to_string());
+        let (kind, detail) = if st.is_member(symbol) {
+            (CompletionItemKind::FIELD, ttype)
+        } else if symbol.is_parameter {
+            let detail = match ttype {
+                Some(ttype) => format!("(parameter) {ttype}"),
+                None => "(parameter)".to_string(),
+            };
+            (CompletionItemKind::VARIABLE, Some(detail))
+        } else {
+            (CompletionItemKind::VARIABLE, ttype)
+        };
+        items.push(CompletionItem {
+            label: symbol.name.clone(),
+            kind: Some(kind),
+            detail,
+            ..Default::default()
+        });
+    }
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        if child.kind() != "function_definition" {
+            continue;
+        }
+        let Some(name_node) = child.child_by_field_name("name") else {
+            continue;
+        };
+        items.push(CompletionItem {
+            label: node_content(&name_node, &file).to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: Some(function_header(child, &file).to_string()),
+            documentation: doc_comment(child, &file).map(Documentation::String),
+            ..Default::default()
+        });
+    }
+    if let Some(parent) = st.class_parent() {
+        add_class_members(&mut items, typedb, parent);
+    }
+    for scope in GLOBAL_SCOPES {
+        add_class_members(&mut items, typedb, &SymbolType::Object(scope.to_string()));
+    }
+    items
+}
+
+/// Returns declared type of the variable or parameter assigned in `var dir: Direction = `,
+/// `func turn(dir: Direction = ` or `dir = `, the line is taken up to the cursor
+/// because the statement being typed usually doesn't parse yet
+fn assignment_target_type(
+    line: &str,
+    st: &SymbolTable,
+    scope_id: usize,
+    position: usize,
+) -> Option<String> {
+    let target = line.trim_end().strip_suffix('=')?;
+    if target.ends_with(['=', '!', '<', '>', ':', '+', '-', '*', '/', '%']) {
+        return None;
+    }
+    let is_identifier = |text: &str| {
+        !text.is_empty()
+            && text
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+    match target.rsplit_once(':') {
+        Some((_, type_name)) => Some(type_name.trim())
+            .filter(|name| is_identifier(name))
+            .map(str::to_string),
+        None => {
+            let name = target.trim();
+            let name = name.strip_prefix("var ").unwrap_or(name).trim();
+            if !is_identifier(name) {
+                return None;
+            }
+            let symbols = st.visible_symbols(scope_id, position);
+            let symbol = symbols.iter().find(|symbol| symbol.name == name)?;
+            Some(symbol.ttype.as_ref()?.to_string())
+        }
+    }
+}
+
+/// Returns names of the members of the enum declared in the script, `Player.State`
+/// is looked up by its last segment
+fn enum_members(root: Node, file: &str, enum_name: &str) -> Vec<String> {
+    let enum_name = enum_name.rsplit('.').next().unwrap_or(enum_name);
+    let mut members = Vec::new();
+    walk_tree(root, &mut |node| {
+        let is_enum = node.kind() == "enum_definition"
+            && node
+                .child_by_field_name("name")
+                .is_some_and(|name| node_content(&name, file) == enum_name);
+        if !is_enum {
+            return;
+        }
+        let Some(body) = node.child_by_field_name("body") else {
+            return;
+        };
+        let mut cursor = body.walk();
+        for enumerator in body.named_children(&mut cursor) {
+            if let Some(name) = enumerator
+                .child_by_field_name("left")
+                .or(enumerator.named_child(0))
+            {
+                members.push(node_content(&name, file).to_string());
+            }
+        }
+    });
+    members
+}
+
+/// Returns node path typed after `$` or `$"` right before the cursor
+fn node_path_before(text: &str) -> Option<&str> {
+    let path_start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '/')
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    let before_path = text[..path_start]
+        .strip_suffix('"')
+        .unwrap_or(&text[..path_start]);
+    before_path.ends_with('$').then_some(&text[path_start..])
+}
+
+/// Finds the expression that ends right before the `.` at `dot_byte`
+fn receiver_node(root: Node, dot_byte: usize) -> Option<Node> {
+    let receiver_end = dot_byte.checked_sub(1)?;
+    let mut node = root.descendant_for_byte_range(receiver_end, receiver_end)?;
+    while let Some(parent) = node.parent() {
+        if parent.end_byte() != node.end_byte()
+            || !matches!(
+                parent.kind(),
+                "attribute" | "call" | "subscript" | "parenthesized_expression"
+            )
+        {
+            break;
+        }
+        node = parent;
+    }
+    Some(node)
+}
+
+/// Adds methods, properties and constants of the class and its ancestors
+fn add_class_members(items: &mut Vec<CompletionItem>, typedb: &TypeDatabase, class: &SymbolType) {
+    let class = &class.untyped();
+    let mut seen = HashSet::new();
+    for class_type in std::iter::once(class).chain(typedb.inheritance_chain(class)) {
+        let Some(class_info) = typedb.classes.get(class_type) else {
+            break;
+        };
+        let class_name = class_type.to_string();
+        let members = class_info
+            .methods
+            .keys()
+            .map(|name| (name, CompletionItemKind::METHOD))
+            .chain(
+                class_info
+                    .properties
+                    .keys()
+                    .map(|name| (name, CompletionItemKind::PROPERTY)),
+            )
+            .chain(
+                class_info
+                    .constants
+                    .keys()
+                    .map(|name| (name, CompletionItemKind::CONSTANT)),
+            );
+        for (name, kind) in members {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let data = CompletionData {
+                class: class_name.clone(),
+                name: name.clone(),
+            };
+            items.push(CompletionItem {
+                label: name.clone(),
+                kind: Some(kind),
+                data: serde_json::to_value(data).ok(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Fills in detail and documentation of a class member item
+pub fn resolve_completion_item(
+    mut item: CompletionItem,
+    typedb: &TypeDatabase,
+    markdown: bool,
+) -> CompletionItem {
+    let Some(data) = item
+        .data
+        .clone()
+        .and_then(|data| serde_json::from_value::<CompletionData>(data).ok())
+    else {
+        return item;
+    };
+    let Some(class) = typedb.classes.get(&SymbolType::from_str(&data.class)) else {
+        return item;
+    };
+    let detail = if let Some(method) = class.methods.get(&data.name) {
+        method.signature(&data.name)
+    } else if let Some(property) = class.properties.get(&data.name) {
+        format!("var {}: {}", data.name, property.ttype.to_string())
+    } else if let Some(constant) = class.constants.get(&data.name) {
+        format!("const {} = {}", data.name, constant.value)
+    } else {
+        return item;
+    };
+    let value = format!("```gdscript\n{detail}\n```\nDefined in `{}`", data.class);
+    item.documentation = Some(if markdown {
+        Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        })
+    } else {
+        Documentation::String(markdown_to_plain_text(&value))
+    });
+    item.detail = Some(detail);
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+    use serde_json::json;
+
+    use crate::{
+        cancellation::CancellationToken, filedb::FileDatabase, scenedb::SceneDatabase,
+        symbol_table::SymbolTable, test_utils::TEST_TYPEDB, utils::parse_file,
+    };
+
+    use super::{make_completions, resolve_completion_item};
+
+    #[test]
+    fn completion_items_are_lazy() {
+        let file = "extends Node
+func foo():
+\tvar speed = 10
+\t";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(3, 1),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &CancellationToken::default(),
+        );
+        assert!(items.iter().any(|item| item.label == "speed"));
+        let get_tree = items.iter().find(|item| item.label == "get_tree").unwrap();
+        assert!(get_tree.documentation.is_none());
+        assert!(get_tree.data.is_some());
+    }
+
+    #[test]
+    fn multibyte_text_before_cursor() {
+        // the cursor character counts UTF-16 units, `é` takes two bytes
+        let file = "func foo():
+\tprint(\"é\", Vector2.";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(1, 20),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &CancellationToken::default(),
+        );
+        assert!(items.iter().any(|item| item.label == "normalized"));
+    }
+
+    #[test]
+    fn symbol_kinds_of_locals_parameters_and_members() {
+        let file = "extends Node
+var health = 100
+func heal(amount: int, source):
+\tvar total = health + amount
+\t";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(4, 1),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &CancellationToken::default(),
+        );
+        let item = |label: &str| {
+            let item = items.iter().find(|item| item.label == label).unwrap();
+            (item.kind, item.detail.as_deref())
+        };
+        assert_eq!(
+            item("total"),
+            (Some(CompletionItemKind::VARIABLE), Some("int"))
+        );
+        assert_eq!(
+            item("amount"),
+            (Some(CompletionItemKind::VARIABLE), Some("(parameter) int"))
+        );
+        assert_eq!(
+            item("source"),
+            (Some(CompletionItemKind::VARIABLE), Some("(parameter)"))
+        );
+        assert_eq!(
+            item("health"),
+            (Some(CompletionItemKind::FIELD), Some("int"))
+        );
+        assert_eq!(item("heal").0, Some(CompletionItemKind::FUNCTION));
+    }
+
+    #[test]
+    fn resolve_populates_documentation() {
+        let item = CompletionItem {
+            label: "get_tree".to_string(),
+            data: Some(json!({ "class": "Node", "name": "get_tree" })),
+            ..Default::default()
+        };
+        let item = resolve_completion_item(item, &TEST_TYPEDB, true);
+        assert_eq!(item.detail.as_deref(), Some("func get_tree() -> SceneTree"));
+        let Some(Documentation::MarkupContent(documentation)) = item.documentation else {
+            panic!("documentation is not populated");
+        };
+        assert!(documentation.value.contains("SceneTree"));
+    }
+
+    #[test]
+    fn node_path_completion_from_scene() {
+        let scene = "[ext_resource type=\"Script\" path=\"res://player.gd\" id=\"1\"]
+[node name=\"Player\" type=\"CharacterBody3D\"]
+script = ExtResource(\"1\")
+[node name=\"Body\" type=\"MeshInstance3D\" parent=\".\"]
+[node name=\"Sprite\" type=\"Sprite3D\" parent=\"Body\"]
+[node name=\"Camera\" type=\"Camera3D\" parent=\".\"]";
+        let scenedb = SceneDatabase::default();
+        scenedb.update_scene(std::path::Path::new("/project"), scene);
+        let file = "extends CharacterBody3D
+func _ready():
+\tprint($Body/)";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/player.gd", file.to_string())
+            .unwrap();
+
+        let labels = |position| {
+            make_completions(
+                "/project/player.gd",
+                position,
+                &TEST_TYPEDB,
+                &filedb,
+                &scenedb,
+                &CancellationToken::default(),
+            )
+            .into_iter()
+            .map(|item| item.label)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(Position::new(2, 8)), vec!["Body", "Camera"]);
+        assert_eq!(labels(Position::new(2, 13)), vec!["Sprite"]);
+    }
+
+    #[test]
+    fn typed_dictionary_members() {
+        let file = "func foo():
+\tvar scores: Dictionary[String, int] = {}
+\tscores.";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(2, 8),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &CancellationToken::default(),
+        );
+        assert!(items.iter().any(|item| item.label == "keys"));
+        assert!(items.iter().any(|item| item.label == "has"));
+    }
+
+    #[test]
+    fn cancelled_completion_stops_analysis() {
+        let file = (0..5000)
+            .map(|i| format!("func foo_{i}():\n\tvar a = Vector3.ZERO\n\treturn a.length()\n"))
+            .collect::<String>();
+        let cancellation = CancellationToken::default();
+        cancellation.cancel();
+
+        // the build loop checks the token, so none of the functions get a scope
+        let tree = parse_file(&file).unwrap();
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        st.set_cancellation(cancellation.clone());
+        st.build_table(&tree, &file);
+        assert_eq!(st.map.len(), 1);
+
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(1, 1),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &cancellation,
+        );
+        assert!(items.is_empty());
+    }
+
+    #[test]
+    fn enum_members_after_typed_assignment() {
+        let file = "enum Direction { UP, DOWN = 5 }
+func foo():
+\tvar dir: Direction = 
+\tvar count: int = ";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let labels = |position| {
+            make_completions(
+                "/test.gd",
+                position,
+                &TEST_TYPEDB,
+                &filedb,
+                &SceneDatabase::default(),
+                &CancellationToken::default(),
+            )
+            .into_iter()
+            .filter(|item| item.kind == Some(CompletionItemKind::ENUM_MEMBER))
+            .map(|item| item.label)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels(Position::new(2, 22)),
+            vec!["Direction.UP", "Direction.DOWN"]
+        );
+        assert!(labels(Position::new(3, 18)).is_empty());
+    }
+
+    #[test]
+    fn gdscript_functions_are_completed() {
+        let file = "func foo(x):
+\t";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let items = make_completions(
+            "/test.gd",
+            Position::new(1, 1),
+            &TEST_TYPEDB,
+            &filedb,
+            &SceneDatabase::default(),
+            &CancellationToken::default(),
+        );
+        for function in ["assert", "print_debug", "len", "preload"] {
+            assert!(
+                items.iter().any(|item| item.label == function),
+                "{function} is not completed"
+            );
+        }
+    }
+}
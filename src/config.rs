@@ -0,0 +1,151 @@
+use async_lsp::lsp_types::{ClientCapabilities, MarkupKind};
+use serde::Deserialize;
+
+/// Server settings passed by the client in `initializationOptions`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Name of the function created by extract refactor, `%d` is replaced with a number
+    /// making the name unique in the file
+    pub extract_function_name: String,
+    /// Where extract refactor places the new function
+    pub extract_insert_position: ExtractInsertPosition,
+    /// Type database dumped from the project's engine build, relative to the workspace root
+    pub type_info_path: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            extract_function_name: "fun_name".to_string(),
+            extract_insert_position: ExtractInsertPosition::default(),
+            type_info_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtractInsertPosition {
+    /// Right after the function the code is extracted from
+    #[default]
+    AfterEnclosingFunction,
+    /// After the last member of the script
+    EndOfClass,
+}
+
+impl Config {
+    pub fn from_initialization_options(options: Option<serde_json::Value>) -> Self {
+        options
+            .and_then(|options| serde_json::from_value(options).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Features of the client read from `InitializeParams.capabilities`,
+/// the unsupported ones fall back to plain text
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClientFeatures {
+    /// Hover contents can be rendered as markdown
+    pub markdown_hover: bool,
+    /// Documentation of completion items can be rendered as markdown
+    pub markdown_documentation: bool,
+    /// Workspace edits can be snippets, the `experimental.snippetTextEdit` extension
+    pub snippet_support: bool,
+    /// `workspace/didChangeWatchedFiles` can be registered dynamically
+    pub watched_files_registration: bool,
+}
+
+impl ClientFeatures {
+    pub fn from_capabilities(capabilities: &ClientCapabilities) -> Self {
+        let supports_markdown = |formats: Option<&Vec<MarkupKind>>| {
+            formats.is_some_and(|formats| formats.contains(&MarkupKind::Markdown))
+        };
+        let text_document = capabilities.text_document.as_ref();
+        let hover_formats = text_document
+            .and_then(|text_document| text_document.hover.as_ref())
+            .and_then(|hover| hover.content_format.as_ref());
+        let documentation_formats = text_document
+            .and_then(|text_document| text_document.completion.as_ref())
+            .and_then(|completion| completion.completion_item.as_ref())
+            .and_then(|item| item.documentation_format.as_ref());
+        let watched_files_registration = capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files.as_ref())
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        let snippet_support = capabilities
+            .experimental
+            .as_ref()
+            .and_then(|experimental| experimental.get("snippetTextEdit"))
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or(false);
+        Self {
+            markdown_hover: supports_markdown(hover_formats),
+            markdown_documentation: supports_markdown(documentation_formats),
+            snippet_support,
+            watched_files_registration,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::ClientCapabilities;
+    use serde_json::json;
+
+    use super::{ClientFeatures, Config, ExtractInsertPosition};
+
+    #[test]
+    fn read_initialization_options() {
+        let config = Config::from_initialization_options(Some(json!({
+            "extractFunctionName": "_extracted_%d"
+        })));
+        assert_eq!(config.extract_function_name, "_extracted_%d");
+
+        let config = Config::from_initialization_options(Some(json!({
+            "extractInsertPosition": "endOfClass"
+        })));
+        assert_eq!(
+            config.extract_insert_position,
+            ExtractInsertPosition::EndOfClass
+        );
+
+        let config = Config::from_initialization_options(Some(json!({
+            "typeInfoPath": "tools/type_info.json"
+        })));
+        assert_eq!(
+            config.type_info_path.as_deref(),
+            Some("tools/type_info.json")
+        );
+
+        let config = Config::from_initialization_options(None);
+        assert_eq!(config.extract_function_name, "fun_name");
+        assert_eq!(
+            config.extract_insert_position,
+            ExtractInsertPosition::AfterEnclosingFunction
+        );
+    }
+
+    #[test]
+    fn read_client_capabilities() {
+        let capabilities: ClientCapabilities = serde_json::from_value(json!({
+            "textDocument": {
+                "hover": { "contentFormat": ["markdown", "plaintext"] },
+                "completion": { "completionItem": { "documentationFormat": ["plaintext"] } }
+            },
+            "workspace": { "didChangeWatchedFiles": { "dynamicRegistration": true } },
+            "experimental": { "snippetTextEdit": true }
+        }))
+        .unwrap();
+        let features = ClientFeatures::from_capabilities(&capabilities);
+        assert!(features.markdown_hover);
+        assert!(!features.markdown_documentation);
+        assert!(features.snippet_support);
+        assert!(features.watched_files_registration);
+
+        let features = ClientFeatures::from_capabilities(&ClientCapabilities::default());
+        assert_eq!(features, ClientFeatures::default());
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    filedb::FileDatabase,
+    utils::{node_content, node_to_range, position_to_point, uri_to_path},
+};
+
+/// Debugging functions a `print` call can be turned into, they take the same arguments
+const PRINT_REPLACEMENTS: [&str; 3] = ["print_debug", "push_warning", "push_error"];
+
+/// Offers to replace the callee of the `print(...)` call under the cursor,
+/// the arguments are kept as is
+pub fn convert_print_actions(
+    params: &CodeActionParams,
+    filedb: &FileDatabase,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(&uri_to_path(uri)) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let point = position_to_point(params.range.start);
+    let mut node = source_file
+        .tree
+        .root_node()
+        .descendant_for_point_range(point, point);
+    // the cursor can be inside of an argument like `str(value)` in `print(str(value))`
+    let callee = loop {
+        let Some(cur_node) = node else {
+            return Vec::new();
+        };
+        if cur_node.kind() == "call"
+            && let Some(callee) = cur_node.child(0)
+            && callee.kind() == "identifier"
+            && node_content(&callee, &file) == "print"
+        {
+            break callee;
+        }
+        node = cur_node.parent();
+    };
+
+    PRINT_REPLACEMENTS
+        .iter()
+        .map(|replacement| {
+            let edit = TextEdit::new(node_to_range(&callee), replacement.to_string());
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Convert `print` to `{replacement}`"),
+                kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{apply_action, code_action_params, test_filedb};
+
+    use super::convert_print_actions;
+
+    #[test]
+    fn print_call_becomes_push_warning() {
+        let file = "func foo(speed):
+\tprint(\"speed: \", str(speed))";
+        let params = code_action_params(Range::new(Position::new(1, 24), Position::new(1, 24)));
+        let actions = convert_print_actions(&params, &test_filedb(file));
+        assert_eq!(actions.len(), 3);
+        let CodeActionOrCommand::CodeAction(action) = &actions[1] else {
+            panic!("expected code action");
+        };
+        assert_eq!(action.title, "Convert `print` to `push_warning`");
+        assert_eq!(
+            apply_action(file, actions.get(1)).unwrap(),
+            "func foo(speed):
+\tpush_warning(\"speed: \", str(speed))"
+        );
+    }
+}
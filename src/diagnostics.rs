@@ -0,0 +1,683 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::{
+    filedb::FileDatabase,
+    symbol_table::{Symbol, SymbolTable, has_static_keyword},
+    typedb::{SymbolType, TypeDatabase},
+    utils::{
+        ParseError, node_content, node_to_utf16_range, point_to_utf16_position, position_to_byte,
+        position_to_point, walk_tree,
+    },
+};
+
+pub const DIAGNOSTIC_SOURCE: &str = "godot-sidekick";
+// Warning codes are named after Godot's warnings, so `@warning_ignore` accepts the same names
+pub const UNUSED_VARIABLE: &str = "unused_variable";
+pub const UNREACHABLE_CODE: &str = "unreachable_code";
+pub const SHADOWED_VARIABLE: &str = "shadowed_variable";
+pub const UNASSIGNED_VARIABLE: &str = "unassigned_variable";
+
+pub fn make_diagnostics(
+    path: &str,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+) -> Vec<Diagnostic> {
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let Ok(uri) = Url::from_file_path(path) else {
+        return Vec::new();
+    };
+    collect_diagnostics(&uri, &source_file.tree, &file, typedb)
+}
+
+/// Reported when the file has no syntax tree at all, so no other diagnostics can be made
+pub fn parse_failure_diagnostic(error: &ParseError) -> Diagnostic {
+    Diagnostic {
+        range: Range::default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some(DIAGNOSTIC_SOURCE.to_string()),
+        message: format!("File couldn't be parsed: {error}"),
+        ..Default::default()
+    }
+}
+
+pub fn collect_diagnostics(
+    uri: &Url,
+    tree: &Tree,
+    file: &str,
+    typedb: &TypeDatabase,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    collect_unreachable_code(tree.root_node(), file, &mut diagnostics);
+
+    let mut st = SymbolTable::new(typedb);
+    st.build_table(tree, file);
+    collect_static_context_errors(&st, tree.root_node(), file, &mut diagnostics);
+    collect_editor_only_usage(&st, tree.root_node(), file, typedb, &mut diagnostics);
+    collect_redeclarations(&st, tree.root_node(), file, uri, &mut diagnostics);
+    collect_unused_variables(&st, tree.root_node(), file, &mut diagnostics);
+    collect_use_before_assignment(&st, tree.root_node(), file, &mut diagnostics);
+    let lines = file.lines().collect::<Vec<_>>();
+    diagnostics.retain(|diagnostic| !is_warning_ignored(tree.root_node(), &lines, diagnostic));
+    diagnostics
+}
+
+/// Checks if the statement with the diagnostic or one of the enclosing statements,
+/// e.g. the function, is preceded by `@warning_ignore("code")`
+fn is_warning_ignored(root: Node, lines: &[&str], diagnostic: &Diagnostic) -> bool {
+    let Some(NumberOrString::String(code)) = &diagnostic.code else {
+        return false;
+    };
+    let quoted_code = format!("\"{code}\"");
+    let is_ignore_annotation = |row: usize| {
+        lines.get(row).is_some_and(|line| {
+            line.trim_start().starts_with("@warning_ignore") && line.contains(&quoted_code)
+        })
+    };
+    // diagnostic columns count UTF-16 code units, tree-sitter columns are bytes
+    let start = diagnostic.range.start;
+    let column = lines.get(start.line as usize).map_or(0, |line| {
+        position_to_byte(line, Position::new(0, start.character))
+    });
+    let point = Point::new(start.line as usize, column);
+    let Some(mut node) = root.descendant_for_point_range(point, point) else {
+        return false;
+    };
+    while let Some(parent) = node.parent() {
+        if matches!(parent.kind(), "source" | "body" | "class_body") {
+            // the statement's own line and the annotation lines right above it
+            let mut row = node.start_position().row;
+            loop {
+                if is_ignore_annotation(row) {
+                    return true;
+                }
+                let Some(previous) = row.checked_sub(1) else {
+                    break;
+                };
+                if !lines[previous].trim_start().starts_with('@') {
+                    break;
+                }
+                row = previous;
+            }
+        }
+        node = parent;
+    }
+    false
+}
+
+/// Range of the symbol name, the hint of a declaration is placed right after its name.
+/// Hint columns are bytes, the range is converted to UTF-16 code units for the client
+fn symbol_name_range(symbol: &Symbol, root: Node, file: &str) -> Range {
+    let end = position_to_point(symbol.hint_position);
+    let start = Point::new(end.row, end.column.saturating_sub(symbol.name.len()));
+    match root.descendant_for_point_range(start, end) {
+        Some(node) => node_to_utf16_range(&node, file),
+        None => Range::new(
+            point_to_utf16_position(file, start),
+            point_to_utf16_position(file, end),
+        ),
+    }
+}
+
+/// Flags variables declared twice in the same scope and locals shadowing variables
+/// of the enclosing scopes, both point to the first declaration in related information
+fn collect_redeclarations(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    uri: &Url,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut redeclarations = Vec::new();
+    for scope in st.map.values() {
+        let declarations = scope
+            .vars
+            .iter()
+            .filter(|var| var.kind == InlayHintKind::TYPE)
+            .collect::<Vec<_>>();
+        for (index, symbol) in declarations.iter().enumerate() {
+            let duplicate = declarations[..index]
+                .iter()
+                .find(|var| var.name == symbol.name);
+            let (original, severity, code, message) = if let Some(original) = duplicate {
+                (
+                    original,
+                    DiagnosticSeverity::ERROR,
+                    None,
+                    format!(
+                        "Variable `{}` is already declared in this scope",
+                        symbol.name
+                    ),
+                )
+            } else {
+                let shadowed = std::iter::successors(st.map.get(&scope.parent), |parent| {
+                    st.map.get(&parent.parent)
+                })
+                .find_map(|parent| {
+                    parent.vars.iter().find(|var| {
+                        var.kind == InlayHintKind::TYPE
+                            && var.name == symbol.name
+                            && var.byte < symbol.byte
+                    })
+                });
+                let Some(original) = shadowed else {
+                    continue;
+                };
+                (
+                    original,
+                    DiagnosticSeverity::WARNING,
+                    Some(NumberOrString::String(SHADOWED_VARIABLE.to_string())),
+                    format!(
+                        "Variable `{}` shadows a variable declared in an outer scope",
+                        symbol.name
+                    ),
+                )
+            };
+            redeclarations.push(Diagnostic {
+                range: symbol_name_range(symbol, root, file),
+                severity: Some(severity),
+                code,
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message,
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location::new(uri.clone(), symbol_name_range(original, root, file)),
+                    message: format!("`{}` is first declared here", symbol.name),
+                }]),
+                ..Default::default()
+            });
+        }
+    }
+    // scopes are stored in a map, so the order is restored from positions
+    redeclarations.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics.extend(redeclarations);
+}
+
+/// Flags local variables that are never referenced after the declaration,
+/// names starting with an underscore are unused on purpose
+fn collect_unused_variables(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // references of all symbols are counted in one walk instead of a walk per variable
+    let mut reference_counts = HashMap::new();
+    walk_tree(root, &mut |node| {
+        if !matches!(node.kind(), "identifier" | "name") {
+            return;
+        }
+        if let Some(symbol) = st.resolve_symbol(node, file) {
+            *reference_counts.entry(symbol).or_insert(0) += 1;
+        }
+    });
+    walk_tree(root, &mut |node| {
+        let is_local = node.kind() == "variable_statement"
+            && node.parent().is_some_and(|parent| parent.kind() == "body");
+        if !is_local {
+            return;
+        }
+        let Some(name_node) = node.child_by_field_name("name") else {
+            return;
+        };
+        let name = node_content(&name_node, file);
+        if name.starts_with('_') {
+            return;
+        }
+        let Some(symbol) = st.resolve_symbol(name_node, file) else {
+            return;
+        };
+        // the declaration itself is the only reference
+        if reference_counts
+            .get(&symbol)
+            .is_some_and(|count| *count > 1)
+        {
+            return;
+        }
+        diagnostics.push(Diagnostic {
+            range: node_to_utf16_range(&name_node, file),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(UNUSED_VARIABLE.to_string())),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: format!("Variable `{name}` is declared but never used"),
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Default::default()
+        });
+    });
+}
+
+/// Flags the first read of a local declared without a value when it comes before any
+/// assignment, the variable holds `null` at that point. Locals typed as built-in value types
+/// like `int`, `Vector2` or `Array` are default-initialized instead and never reported.
+/// Only reads in the scope of the declaration are reported because a nested block
+/// may run conditionally
+fn collect_use_before_assignment(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // first usages of all symbols are found in one walk instead of a walk per variable,
+    // symbols are only visible after their declaration, so every usage follows it
+    let mut first_usages = HashMap::new();
+    walk_tree(root, &mut |node| {
+        let is_declaration = node.parent().is_some_and(|parent| {
+            parent.kind() == "variable_statement"
+                && parent.child_by_field_name("name") == Some(node)
+        });
+        if node.kind() != "identifier" || is_declaration {
+            return;
+        }
+        if let Some(symbol) = st.resolve_symbol(node, file) {
+            first_usages.entry(symbol).or_insert(node);
+        }
+    });
+    walk_tree(root, &mut |statement| {
+        let is_uninitialized_local = statement.kind() == "variable_statement"
+            && statement.child_by_field_name("value").is_none()
+            && statement
+                .parent()
+                .is_some_and(|parent| parent.kind() == "body");
+        if !is_uninitialized_local {
+            return;
+        }
+        let Some(name_node) = statement.child_by_field_name("name") else {
+            return;
+        };
+        let name = node_content(&name_node, file);
+        let Some(symbol) = st.resolve_symbol(name_node, file) else {
+            return;
+        };
+        let is_default_initialized = st.map.get(&symbol.0).is_some_and(|scope| {
+            let declared = &scope.vars[symbol.1];
+            declared.static_typed
+                && matches!(
+                    declared.ttype,
+                    Some(
+                        SymbolType::Variant(_)
+                            | SymbolType::Array(_)
+                            | SymbolType::ObjectArray(_)
+                            | SymbolType::Dictionary(_, _)
+                    )
+                )
+        });
+        if is_default_initialized {
+            return;
+        }
+        let Some(&usage) = first_usages.get(&symbol) else {
+            return;
+        };
+        // `v.x = 1` and `a[0] = 1` assign through the variable as well
+        let mut target = usage;
+        while let Some(parent) = target
+            .parent()
+            .filter(|parent| matches!(parent.kind(), "attribute" | "subscript"))
+            .filter(|parent| parent.child(0) == Some(target))
+        {
+            target = parent;
+        }
+        let is_assigned = target.parent().is_some_and(|parent| {
+            parent.kind() == "assignment"
+                && parent.child_by_field_name("left").or(parent.child(0)) == Some(target)
+        });
+        if is_assigned || st.scope_of(usage) != symbol.0 {
+            return;
+        }
+        diagnostics.push(Diagnostic {
+            range: node_to_utf16_range(&usage, file),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(UNASSIGNED_VARIABLE.to_string())),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: format!("Variable `{name}` is used before being assigned"),
+            ..Default::default()
+        });
+    });
+}
+
+fn is_control_flow_exit(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        "return_statement" | "break_statement" | "continue_statement"
+    )
+}
+
+/// Flags statements that follow an unconditional `return`/`break`/`continue` in the same block.
+/// Only direct children of a block are considered, so an exit inside one branch of an `if`
+/// doesn't affect the statements after the `if` itself.
+fn collect_unreachable_code(node: Node, file: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut cursor = node.walk();
+    let mut exit_found = false;
+    let mut unreachable: Option<(Node, Node)> = None;
+    for child in node.named_children(&mut cursor) {
+        if child.kind() == "comment" {
+            continue;
+        }
+        if exit_found {
+            unreachable = match unreachable {
+                Some((first, _)) => Some((first, child)),
+                None => Some((child, child)),
+            };
+        } else if node.kind() == "body" && is_control_flow_exit(child) {
+            exit_found = true;
+        }
+        collect_unreachable_code(child, file, diagnostics);
+    }
+
+    if let Some((first, last)) = unreachable {
+        diagnostics.push(Diagnostic {
+            range: Range::new(
+                point_to_utf16_position(file, first.start_position()),
+                point_to_utf16_position(file, last.end_position()),
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(UNREACHABLE_CODE.to_string())),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: "Unreachable code".to_string(),
+            tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+            ..Default::default()
+        });
+    }
+}
+
+/// Names of the functions declared in the class without `static` keyword
+fn instance_functions<'f>(root: Node, file: &'f str) -> Vec<&'f str> {
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|child| child.kind() == "function_definition")
+        .filter(|function| !has_static_keyword(*function))
+        .filter_map(|function| function.child_by_field_name("name"))
+        .map(|name| node_content(&name, file))
+        .collect()
+}
+
+/// Flags usage of `self`, instance variables and instance functions inside static functions
+fn collect_static_context_errors(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let instance_functions = instance_functions(root, file);
+    walk_tree(root, &mut |node| {
+        if !matches!(node.kind(), "identifier" | "self") {
+            return;
+        }
+        let scope_id = st.scope_of(node);
+        if !st.map.get(&scope_id).is_some_and(|scope| scope.is_static) {
+            return;
+        }
+        let Some(parent) = node.parent() else {
+            return;
+        };
+        // members accessed through `.` are checked by looking at their left hand side
+        if parent.kind() == "attribute" && parent.child(0) != Some(node) {
+            return;
+        }
+
+        let name = node_content(&node, file);
+        let message = if name == "self" {
+            "Cannot use `self` inside a static function".to_string()
+        } else if parent.kind() == "call"
+            && parent.child(0) == Some(node)
+            && instance_functions.contains(&name)
+        {
+            format!("Cannot call non-static function `{name}` from a static function")
+        } else if let Some((symbol_scope, index)) = st.resolve_symbol(node, file)
+            && symbol_scope == st.root_scope()
+            && !st.map[&symbol_scope].vars[index].is_static
+        {
+            format!("Cannot access instance variable `{name}` from a static function")
+        } else {
+            return;
+        };
+        diagnostics.push(Diagnostic {
+            range: node_to_utf16_range(&node, file),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message,
+            ..Default::default()
+        });
+    });
+}
+
+/// Flags usage of editor classes like `EditorInterface` in scripts that don't run in the editor,
+/// `@tool` scripts and editor plugins/scripts extending an editor class are allowed to use them
+fn collect_editor_only_usage(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    typedb: &TypeDatabase,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let extends_editor_class = st
+        .class_parent()
+        .is_some_and(|parent| parent.to_string().starts_with("Editor"));
+    if st.is_tool() || extends_editor_class {
+        return;
+    }
+    walk_tree(root, &mut |node| {
+        if node.kind() != "identifier" {
+            return;
+        }
+        let name = node_content(&node, file);
+        if !name.starts_with("Editor") || !typedb.classes.contains_key(&SymbolType::from_str(name))
+        {
+            return;
+        }
+        diagnostics.push(Diagnostic {
+            range: node_to_utf16_range(&node, file),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: format!("`{name}` is only available in the editor, add @tool to the script"),
+            ..Default::default()
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    use crate::{
+        test_utils::{TEST_TYPEDB, test_uri},
+        utils::parse_file,
+    };
+
+    use super::collect_diagnostics;
+
+    #[test]
+    fn statement_after_return_is_unreachable() {
+        let file = "func foo():
+\tvar a = 10
+\treturn a
+\tprint(a)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(3, 1));
+    }
+
+    #[test]
+    fn return_in_if_branch_does_not_affect_siblings() {
+        let file = "func foo(a):
+\tif a:
+\t\treturn 1
+\telse:
+\t\tprint(a)
+\tprint(a)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn instance_members_in_static_function() {
+        let file = "var health = 10
+const MAX = 5
+static var count = 0
+func heal():
+\tpass
+static func foo():
+\tprint(self)
+\tprint(health, MAX, count)
+\theal()";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        let starts: Vec<_> = diagnostics.iter().map(|d| d.range.start).collect();
+        assert_eq!(
+            starts,
+            vec![
+                Position::new(6, 7),
+                Position::new(7, 7),
+                Position::new(8, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn tool_script_may_use_editor_classes() {
+        let file = "extends Node
+func _ready():
+\tprint(EditorInterface.get_edited_scene_root())";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(2, 7));
+
+        let file = format!("@tool\n{file}");
+        let tree = parse_file(&file).unwrap();
+        assert!(collect_diagnostics(&test_uri(), &tree, &file, &TEST_TYPEDB).is_empty());
+    }
+
+    #[test]
+    fn shadowing_points_to_first_declaration() {
+        let file = "var speed = 10
+func foo(amount: int):
+\tvar speed = 5
+\tvar amount = 1
+\tprint(speed, amount)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 2);
+
+        let shadowing = &diagnostics[0];
+        assert_eq!(shadowing.severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            shadowing.range,
+            Range::new(Position::new(2, 5), Position::new(2, 10))
+        );
+        let related = shadowing.related_information.as_ref().unwrap();
+        assert_eq!(related[0].location.uri, test_uri());
+        assert_eq!(
+            related[0].location.range,
+            Range::new(Position::new(0, 4), Position::new(0, 9))
+        );
+
+        let duplicate = &diagnostics[1];
+        assert_eq!(duplicate.severity, Some(DiagnosticSeverity::ERROR));
+        let related = duplicate.related_information.as_ref().unwrap();
+        assert_eq!(
+            related[0].location.range,
+            Range::new(Position::new(1, 9), Position::new(1, 15))
+        );
+    }
+
+    #[test]
+    fn redeclaration_range_counts_utf16_code_units() {
+        let file = "var speed = 10
+func foo():
+\tprint(\"é\"); var speed = 5
+\tprint(speed)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 17), Position::new(2, 22))
+        );
+    }
+
+    #[test]
+    fn unused_local_variable() {
+        let file = "var health = 10
+func foo():
+\tvar used = 1
+\tvar unused = 2
+\tvar _ignored = 3
+\tprint(used)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(3, 5), Position::new(3, 11))
+        );
+    }
+
+    #[test]
+    fn read_before_assignment() {
+        let file = "func foo():
+\tvar x
+\tprint(x)
+\tx = 5
+\tvar y
+\ty = 1
+\tprint(y)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(2, 7), Position::new(2, 8))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn typed_values_and_member_assignments_are_initialized() {
+        let file = "func foo(n):
+\tvar v: Vector2
+\tprint(v)
+\tvar a: Array
+\ta.append(n)
+\tvar i: int
+\ti += 1
+\tvar node: Node
+\tprint(node)
+\tvar point
+\tpoint.x = 1
+\tvar list
+\tlist[0] = n
+\tprint(point, list)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(8, 7), Position::new(8, 11))
+        );
+    }
+
+    #[test]
+    fn warning_ignore_suppresses_next_statement() {
+        let file = "func foo():
+\t@warning_ignore(\"unused_variable\")
+\tvar ignored = 1
+\tvar reported = 2
+
+@warning_ignore(\"unused_variable\", \"unreachable_code\")
+func bar():
+\tvar unused = 1
+\treturn unused
+\tprint(unused)";
+        let tree = parse_file(file).unwrap();
+        let diagnostics = collect_diagnostics(&test_uri(), &tree, file, &TEST_TYPEDB);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(3, 5));
+    }
+}
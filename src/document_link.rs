@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    filedb::FileDatabase,
+    scenedb::resolve_res_path,
+    utils::{node_content, walk_tree},
+};
+
+/// Makes links for string literals with `res://` paths, e.g. in `preload("res://enemy.tscn")`
+/// or `extends "res://base.gd"`, so they open the referenced file
+pub fn make_document_links(
+    path: &str,
+    filedb: &FileDatabase,
+    workspace_root: Option<&Path>,
+) -> Vec<DocumentLink> {
+    let mut links = Vec::new();
+    let Some(workspace_root) = workspace_root else {
+        return links;
+    };
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return links;
+    };
+    let file = source_file.content.to_string();
+
+    walk_tree(source_file.tree.root_node(), &mut |node| {
+        if node.kind() != "string" || node.start_position().row != node.end_position().row {
+            return;
+        }
+        let content = node_content(&node, &file);
+        let quote_len = content.len() - content.trim_start_matches(['"', '\'']).len();
+        let res_path = content.trim_matches(['"', '\'']);
+        if !res_path.starts_with("res://") {
+            return;
+        }
+        let Ok(target) = Url::from_file_path(resolve_res_path(workspace_root, res_path)) else {
+            return;
+        };
+        let start = node.start_position();
+        let start_character = (start.column + quote_len) as u32;
+        links.push(DocumentLink {
+            range: Range::new(
+                Position::new(start.row as u32, start_character),
+                Position::new(
+                    start.row as u32,
+                    start_character + res_path.chars().count() as u32,
+                ),
+            ),
+            target: Some(target),
+            tooltip: Some(res_path.to_string()),
+            data: None,
+        });
+    });
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use async_lsp::lsp_types::{Position, Range, Url};
+
+    use crate::filedb::FileDatabase;
+
+    use super::make_document_links;
+
+    #[test]
+    fn preload_path_is_linked() {
+        let file = "extends Node
+const Enemy = preload(\"res://enemies/enemy.tscn\")
+var title = \"not a path\"";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/main.gd", file.to_string())
+            .unwrap();
+        let links = make_document_links("/project/main.gd", &filedb, Some(Path::new("/project")));
+        assert_eq!(links.len(), 1);
+        assert_eq!(
+            links[0].range,
+            Range::new(Position::new(1, 23), Position::new(1, 47))
+        );
+        assert_eq!(
+            links[0].target,
+            Some(Url::from_file_path("/project/enemies/enemy.tscn").unwrap())
+        );
+    }
+}
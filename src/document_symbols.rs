@@ -0,0 +1,276 @@
+use async_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{
+    filedb::FileDatabase,
+    utils::{annotation_arguments, annotation_name, node_content, node_to_range},
+};
+
+/// Annotations grouping exported variables in the inspector, ordered by nesting level
+const EXPORT_GROUPINGS: [&str; 3] = ["export_category", "export_group", "export_subgroup"];
+
+pub fn make_document_symbols(path: &str, filedb: &FileDatabase) -> Vec<DocumentSymbol> {
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    collect_document_symbols(source_file.tree.root_node(), &file)
+}
+
+/// Exported variables are nested under the `@export_group` and similar annotations
+/// preceding them, so the outline mirrors the inspector
+fn collect_document_symbols(body: Node, file: &str) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    // nesting level and index in the parent's children of each open group, outermost first
+    let mut open_groups: Vec<(usize, usize)> = Vec::new();
+    // `@export` written on its own line before the declaration
+    let mut pending_export = false;
+    let mut cursor = body.walk();
+    for child in body.children(&mut cursor) {
+        let mut is_exported = pending_export;
+        let annotations = match child.kind() {
+            "annotation" | "annotations" => vec![child],
+            "variable_statement" => {
+                let mut cursor = child.walk();
+                child
+                    .children(&mut cursor)
+                    .filter(|node| matches!(node.kind(), "annotation" | "annotations"))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+        for annotation in annotations.into_iter().flat_map(flatten_annotations) {
+            let name = annotation_name(annotation, file);
+            let Some(level) = EXPORT_GROUPINGS
+                .iter()
+                .position(|grouping| *grouping == name)
+            else {
+                is_exported |= name.starts_with("export");
+                continue;
+            };
+            // a group ends at the next group of the same or outer level, an empty name
+            // like `@export_group("")` only ends it
+            open_groups.retain(|(open_level, _)| *open_level < level);
+            let group_name = annotation_arguments(annotation, file)
+                .first()
+                .map_or("", |argument| argument.trim_matches('"'));
+            if group_name.is_empty() {
+                continue;
+            }
+            let group = make_symbol(
+                group_name.to_string(),
+                Some(format!("@{name}")),
+                SymbolKind::NAMESPACE,
+                annotation,
+                annotation,
+                Some(Vec::new()),
+            );
+            let siblings = group_children(&mut symbols, &open_groups, group.range.end);
+            siblings.push(group);
+            open_groups.push((level, siblings.len() - 1));
+        }
+        if matches!(child.kind(), "annotation" | "annotations") {
+            pending_export = is_exported;
+            continue;
+        }
+        if child.kind() != "comment" {
+            pending_export = false;
+        }
+
+        let (kind, children) = match child.kind() {
+            // class-level annotations like `@tool` or `@icon("...")` precede the declarations
+            // they apply to but aren't declarations themselves
+            "comment" => continue,
+            "class_name_statement" => (SymbolKind::CLASS, None),
+            "variable_statement" => (SymbolKind::VARIABLE, None),
+            "const_statement" => (SymbolKind::CONSTANT, None),
+            "signal_statement" => (SymbolKind::EVENT, None),
+            "function_definition" => (SymbolKind::FUNCTION, None),
+            "constructor_definition" => (SymbolKind::CONSTRUCTOR, None),
+            "enum_definition" => (SymbolKind::ENUM, Some(collect_enumerators(child, file))),
+            "class_definition" => {
+                let children = child
+                    .child_by_field_name("body")
+                    .map(|body| collect_document_symbols(body, file));
+                (SymbolKind::CLASS, children)
+            }
+            _ => continue,
+        };
+        let name_node = child.child_by_field_name("name");
+        let name = match name_node {
+            Some(name_node) => node_content(&name_node, file).to_string(),
+            None if child.kind() == "constructor_definition" => "_init".to_string(),
+            None => continue,
+        };
+        let detail = child
+            .child_by_field_name("type")
+            .or(child.child_by_field_name("return_type"))
+            .map(|type_node| node_content(&type_node, file).to_string());
+        let symbol = make_symbol(
+            name,
+            detail,
+            kind,
+            child,
+            name_node.unwrap_or(child),
+            children,
+        );
+        if is_exported && child.kind() == "variable_statement" {
+            group_children(&mut symbols, &open_groups, symbol.range.end).push(symbol);
+        } else {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+fn flatten_annotations(node: Node) -> Vec<Node> {
+    if node.kind() == "annotation" {
+        return vec![node];
+    }
+    let mut cursor = node.walk();
+    node.named_children(&mut cursor)
+        .filter(|child| child.kind() == "annotation")
+        .collect()
+}
+
+/// Returns children of the innermost open group, the ranges of all open groups
+/// are extended to `end` as the returned list gets a new member
+fn group_children<'s>(
+    symbols: &'s mut Vec<DocumentSymbol>,
+    open_groups: &[(usize, usize)],
+    end: Position,
+) -> &'s mut Vec<DocumentSymbol> {
+    let mut children = symbols;
+    for (_, index) in open_groups {
+        let group = &mut children[*index];
+        group.range.end = end;
+        children = group.children.get_or_insert_with(Vec::new);
+    }
+    children
+}
+
+fn collect_enumerators(enum_node: Node, file: &str) -> Vec<DocumentSymbol> {
+    let Some(enumerator_list) = enum_node.child_by_field_name("body") else {
+        return Vec::new();
+    };
+    let mut cursor = enumerator_list.walk();
+    enumerator_list
+        .named_children(&mut cursor)
+        .filter(|node| node.kind() == "enumerator")
+        .filter_map(|enumerator| {
+            let name_node = enumerator
+                .child_by_field_name("left")
+                .or(enumerator.child(0))?;
+            Some(make_symbol(
+                node_content(&name_node, file).to_string(),
+                None,
+                SymbolKind::ENUM_MEMBER,
+                enumerator,
+                name_node,
+                None,
+            ))
+        })
+        .collect()
+}
+
+#[allow(deprecated)]
+fn make_symbol(
+    name: String,
+    detail: Option<String>,
+    kind: SymbolKind,
+    node: Node,
+    name_node: Node,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: node_to_range(&node),
+        selection_range: node_to_range(&name_node),
+        children,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+
+    use crate::utils::parse_file;
+
+    use super::collect_document_symbols;
+
+    #[test]
+    fn exported_variables_nest_under_groups() {
+        let file = "extends Node
+@export_category(\"Player\")
+@export_group(\"Stats\")
+@export var health = 100
+@export
+var speed = 1.0
+var cache = []
+@export_subgroup(\"Regen\")
+@export var regen_rate = 2
+@export_group(\"\")
+@export var title = \"hero\"
+
+func f():
+\tpass";
+        let tree = parse_file(file).unwrap();
+        let symbols = collect_document_symbols(tree.root_node(), file);
+        let names = |symbols: &[DocumentSymbol]| {
+            symbols
+                .iter()
+                .map(|symbol| symbol.name.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&symbols), vec!["Player", "cache", "f"]);
+        let category = &symbols[0];
+        assert_eq!(category.kind, SymbolKind::NAMESPACE);
+        let category_children = category.children.as_ref().unwrap();
+        assert_eq!(names(category_children), vec!["Stats", "title"]);
+        let group = &category_children[0];
+        assert_eq!(
+            names(group.children.as_ref().unwrap()),
+            vec!["health", "speed", "Regen"]
+        );
+        assert_eq!(group.range.start.line, 2);
+        assert_eq!(group.range.end.line, 8);
+        let subgroup = &group.children.as_ref().unwrap()[2];
+        assert_eq!(
+            names(subgroup.children.as_ref().unwrap()),
+            vec!["regen_rate"]
+        );
+        assert_eq!(category.range.end.line, 10);
+    }
+
+    #[test]
+    fn tool_script_outline() {
+        let file = "@tool
+@icon(\"res://icon.svg\")
+class_name Foo
+extends Node
+
+var a = 1
+
+func f():
+\tpass";
+        let tree = parse_file(file).unwrap();
+        let symbols = collect_document_symbols(tree.root_node(), file);
+        let names = symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("Foo", SymbolKind::CLASS),
+                ("a", SymbolKind::VARIABLE),
+                ("f", SymbolKind::FUNCTION)
+            ]
+        );
+    }
+}
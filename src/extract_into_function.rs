@@ -4,83 +4,173 @@ use async_lsp::lsp_types::*;
 use streaming_iterator::StreamingIterator;
 use tree_sitter::{Language, Node, Query, QueryCursor, Tree};
 
-use crate::utils::{node_content, parse_file, point_to_position, position_to_point};
+use crate::{
+    config::{Config, ExtractInsertPosition},
+    filedb::FileDatabase,
+    utils::{clamp_range, node_content, point_to_position, position_to_point, uri_to_path},
+};
+
+pub fn extract_into_function_action(
+    params: &CodeActionParams,
+    config: &Config,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    // code action responses can't carry snippet edits
+    let edit = extract_into_function_edit(
+        &params.text_document.uri,
+        params.range,
+        config,
+        filedb,
+        false,
+    )?;
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Extract into function".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        diagnostics: None,
+        edit: Some(edit),
+        command: None,
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    }))
+}
 
-pub fn extract_into_function_action(params: &CodeActionParams) -> Option<CodeActionOrCommand> {
-    let file_path = params.text_document.uri.path();
-    let range = params.range;
+/// Moves the statements in the range of the file into a new function
+/// and replaces them with a call to it, with `snippets` the edits are snippets
+/// placing the cursor on the name of the new function
+pub fn extract_into_function_edit(
+    uri: &Url,
+    range: Range,
+    config: &Config,
+    filedb: &FileDatabase,
+    snippets: bool,
+) -> Option<WorkspaceEdit> {
     if range.start == range.end {
         return None;
     }
 
-    let file_content = std::fs::read_to_string(file_path).unwrap();
-    let tree = parse_file(&file_content).unwrap();
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file_content = source_file.content.to_string();
+    let range = clamp_range(&source_file.content, range);
+    let tree = &source_file.tree;
 
     let (start_node, end_node) =
         start_end_nodes_from_range(tree.root_node(), range, &file_content)?;
 
-    let mut start_byte = start_node.start_byte();
+    // comments are skipped when looking for selected statements,
+    // but selected comment lines above the first statement are moved with it
+    let mut content_start = start_node;
+    while let Some(comment) = content_start.prev_sibling().filter(|node| {
+        node.kind() == "comment" && node.start_position().row >= range.start.line as usize
+    }) {
+        content_start = comment;
+    }
+    let mut start_byte = content_start.start_byte();
     while start_byte > 0 && file_content.as_bytes()[start_byte - 1] != b'\n' {
         start_byte -= 1;
     }
-    let content = &file_content[start_byte..end_node.end_byte()];
-
-    let insert_pos = find_insert_position(start_node);
-
-    let new_arguments = collect_non_declared_variables(&tree, start_node, end_node, &file_content);
+    // a comment after the last statement on the same line moves together with it
+    let content_end = end_node
+        .next_sibling()
+        .filter(|node| {
+            node.kind() == "comment" && node.start_position().row == end_node.end_position().row
+        })
+        .unwrap_or(end_node);
+    let content = &file_content[start_byte..content_end.end_byte()];
+
+    let insert_pos = find_insert_position(start_node, config.extract_insert_position)?;
+
+    let new_arguments = collect_non_declared_variables(tree, start_node, end_node, &file_content);
     let new_arguments = new_arguments.into_iter().collect::<Vec<_>>().join(", ");
+    let function_name = make_function_name(&config.extract_function_name, tree, &file_content);
 
     let previous_text_replacement = if end_node.kind() == "variable_statement" {
         let name_node = end_node.child_by_field_name("name").unwrap();
         let name = node_content(&name_node, &file_content);
-        format!("var {name} = fun_name({new_arguments})")
+        format!("var {name} = {function_name}({new_arguments})")
     } else {
-        format!("fun_name({})", new_arguments)
+        format!("{function_name}({new_arguments})")
     };
 
     let previous_indent_size = calculate_previous_indent_size(content);
     let previous_indent_str = format!("\n{}", "\t".repeat(previous_indent_size));
 
-    let mut insert_text = format!("\n\n\nfunc fun_name({}):\n{}", new_arguments, content);
+    let separator = "\n".repeat(function_separator_lines(tree.root_node(), &file_content) + 1);
+    let mut insert_text = format!("{separator}func {function_name}({new_arguments}):\n{content}");
     if end_node.kind() == "variable_statement" {
         let name_node = end_node.child_by_field_name("name").unwrap();
         let name = node_content(&name_node, &file_content);
         insert_text += &format!("\n\treturn {name}");
     }
     insert_text = insert_text.replace(&previous_indent_str, "\n\t");
+    let previous_text_replacement = if snippets {
+        insert_text = escape_snippet(&insert_text).replacen(
+            &format!("func {function_name}("),
+            &format!("func ${{0:{function_name}}}("),
+            1,
+        );
+        escape_snippet(&previous_text_replacement)
+    } else {
+        previous_text_replacement
+    };
 
-    Some(CodeActionOrCommand::CodeAction(CodeAction {
-        title: "Extract into function".to_string(),
-        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
-        diagnostics: None,
-        edit: Some(WorkspaceEdit {
-            changes: None,
-            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
-                text_document: OptionalVersionedTextDocumentIdentifier {
-                    uri: async_lsp::lsp_types::Url::from_file_path(file_path).unwrap(),
-                    version: None,
-                },
-                edits: vec![
-                    OneOf::Left(TextEdit::new(
-                        Range::new(
-                            point_to_position(start_node.start_position()),
-                            point_to_position(end_node.end_position()),
-                        ),
-                        previous_text_replacement,
-                    )),
-                    OneOf::Left(TextEdit::new(
-                        Range::new(point_to_position(insert_pos), point_to_position(insert_pos)),
-                        insert_text,
-                    )),
-                ],
-            }])),
-            change_annotations: None,
-        }),
-        command: None,
-        is_preferred: None,
-        disabled: None,
-        data: None,
-    }))
+    Some(WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: None,
+            },
+            edits: vec![
+                OneOf::Left(TextEdit::new(
+                    Range::new(
+                        point_to_position(content_start.start_position()),
+                        point_to_position(content_end.end_position()),
+                    ),
+                    previous_text_replacement,
+                )),
+                OneOf::Left(TextEdit::new(
+                    Range::new(point_to_position(insert_pos), point_to_position(insert_pos)),
+                    insert_text,
+                )),
+            ],
+        }])),
+        change_annotations: None,
+    })
+}
+
+/// Escapes characters having a meaning in snippet syntax, e.g. `$` of node paths
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('$', "\\$")
+        .replace('}', "\\}")
+}
+
+/// Makes name for the new function from the template, `%d` in the template is replaced with
+/// the smallest number that doesn't clash with already defined functions
+fn make_function_name(template: &str, tree: &Tree, file: &str) -> String {
+    let root = tree.root_node();
+    let defined_functions = root
+        .children(&mut root.walk())
+        .filter(|node| node.kind() == "function_definition")
+        .filter_map(|node| node.child_by_field_name("name"))
+        .map(|name_node| node_content(&name_node, file))
+        .collect::<HashSet<_>>();
+
+    if !template.contains("%d") {
+        if !defined_functions.contains(template) {
+            return template.to_string();
+        }
+        return (2..)
+            .map(|i| format!("{template}{i}"))
+            .find(|name| !defined_functions.contains(name.as_str()))
+            .unwrap();
+    }
+    (1..)
+        .map(|i| template.replace("%d", &i.to_string()))
+        .find(|name| !defined_functions.contains(name.as_str()))
+        .unwrap()
 }
 
 fn walk_from_start_to_end_node(start_node: Node, end_node: Node, mut callback: impl FnMut(Node)) {
@@ -118,7 +208,8 @@ fn collect_non_declared_variables(
 fn collect_used_variables(node: Node, file: &str) -> HashSet<String> {
     let query = "(binary_operator (identifier) @used)
 (arguments (identifier) @used)
-(attribute . (identifier) @used)";
+(attribute . (identifier) @used)
+(augmented_assignment (identifier) @used)";
     let query = Query::new(&Language::new(tree_sitter_gdscript::LANGUAGE), query).unwrap();
     let mut cursor = QueryCursor::new();
     let mut captures = cursor.captures(&query, node, file.as_bytes());
@@ -132,13 +223,16 @@ fn collect_used_variables(node: Node, file: &str) -> HashSet<String> {
     names
 }
 
+/// Returns names of the class variables and constants, other top level statements
+/// like comments and annotations are skipped
 fn collect_top_level_variable_definitions(tree: &Tree, file: &str) -> Vec<String> {
-    let start_node = tree.root_node().child(0).unwrap();
-    let end_node = tree
-        .root_node()
-        .child(tree.root_node().child_count() - 1)
-        .unwrap();
-    collect_variable_definitions(start_node, end_node, file)
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|child| matches!(child.kind(), "variable_statement" | "const_statement"))
+        .filter_map(|child| child.child_by_field_name("name"))
+        .map(|name_node| node_content(&name_node, file).to_string())
+        .collect()
 }
 
 fn collect_variable_definitions(start_node: Node, end_node: Node, file: &str) -> Vec<String> {
@@ -155,7 +249,7 @@ fn collect_variable_definitions(start_node: Node, end_node: Node, file: &str) ->
     variables
 }
 
-fn calculate_previous_indent_size(content: &str) -> usize {
+pub(crate) fn calculate_previous_indent_size(content: &str) -> usize {
     let mut previous_indent_size = 0;
     for c in content.chars() {
         if c != '\t' {
@@ -166,41 +260,77 @@ fn calculate_previous_indent_size(content: &str) -> usize {
     previous_indent_size
 }
 
-fn find_insert_position(start_node: Node<'_>) -> tree_sitter::Point {
-    let mut parent = start_node.parent().unwrap();
+/// Returns `None` if the statements aren't in a function, e.g. in a lambda
+/// assigned to a class variable
+fn find_insert_position(
+    start_node: Node<'_>,
+    mode: ExtractInsertPosition,
+) -> Option<tree_sitter::Point> {
+    let mut parent = start_node.parent()?;
     while parent.kind() != "function_definition" {
-        parent = parent.parent().unwrap()
+        parent = parent.parent()?;
     }
-    parent.end_position()
+    if mode == ExtractInsertPosition::EndOfClass {
+        // only top level functions are moved to the end of the script,
+        // functions of inner classes would need to be reindented
+        if let Some(class_body) = parent.parent().filter(|node| node.parent().is_none()) {
+            let last_child = class_body
+                .named_child_count()
+                .checked_sub(1)
+                .and_then(|index| class_body.named_child(index));
+            if let Some(last_child) = last_child {
+                return Some(last_child.end_position());
+            }
+        }
+    }
+    Some(parent.end_position())
+}
+
+/// Returns number of blank lines between top level functions used in the file,
+/// the style guide's two lines are used if the file has no functions to follow
+pub fn function_separator_lines(root: Node, file: &str) -> usize {
+    let lines = file.lines().collect::<Vec<_>>();
+    let mut cursor = root.walk();
+    let members = root
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() != "comment")
+        .collect::<Vec<_>>();
+    members
+        .windows(2)
+        .find(|pair| pair.iter().all(|node| node.kind() == "function_definition"))
+        .map_or(2, |pair| {
+            (pair[0].end_position().row + 1..pair[1].start_position().row)
+                .filter(|row| lines.get(*row).is_some_and(|line| line.trim().is_empty()))
+                .count()
+                .clamp(1, 2)
+        })
 }
 
+/// Moves the position to the first non-whitespace character of its line,
+/// on a blank line the character is only clamped to the line length
 fn get_first_non_whitespace_position(mut position: Position, file: &str) -> Position {
-    let mut line = file;
-    let mut passed_new_lines = 0;
-    for (i, c) in file.chars().enumerate() {
-        if c == '\n' {
-            passed_new_lines += 1;
-        }
-        if passed_new_lines == position.line {
-            line = &file[i + 1..];
-            break;
-        }
-    }
-    for (i, c) in line.chars().enumerate() {
-        if c != '\t' {
-            position.character = i as u32;
-            return position;
-        }
+    let Some(line) = file.lines().nth(position.line as usize) else {
+        return position;
+    };
+    match line.find(|c: char| !c.is_whitespace()) {
+        Some(column) => position.character = column as u32,
+        None => position.character = position.character.min(line.len() as u32),
     }
     position
 }
 
-fn node_from_position<'b>(
-    root_node: Node<'b>,
-    mut position: Position,
-    file: &str,
-) -> Option<Node<'b>> {
-    position = get_first_non_whitespace_position(position, file);
+fn node_from_position<'b>(root_node: Node<'b>, position: Position, file: &str) -> Option<Node<'b>> {
+    let position = get_first_non_whitespace_position(position, file);
+    statement_at(root_node, position).or_else(|| {
+        // retry with the last meaningful character in case the cursor is past the line content
+        let line = file.lines().nth(position.line as usize)?;
+        let last_column = line.trim_end().len().checked_sub(1)?;
+        statement_at(root_node, Position::new(position.line, last_column as u32))
+    })
+}
+
+/// Returns statement of a block that contains the position
+fn statement_at(root_node: Node, position: Position) -> Option<Node> {
     let mut end_node = root_node
         .descendant_for_point_range(position_to_point(position), position_to_point(position));
     while let Some(en) = end_node {
@@ -214,20 +344,49 @@ fn node_from_position<'b>(
     end_node
 }
 
+/// Shrinks the range so it doesn't start or end on a blank or comment-only line,
+/// `None` if there are no other lines in the range
+fn trim_range_to_statements(mut range: Range, file: &str) -> Option<Range> {
+    let lines = file.lines().collect::<Vec<_>>();
+    let is_skipped = |line: u32| {
+        lines.get(line as usize).is_none_or(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with('#')
+        })
+    };
+    while is_skipped(range.start.line) {
+        if range.start.line >= range.end.line {
+            return None;
+        }
+        range.start = Position::new(range.start.line + 1, 0);
+    }
+    while is_skipped(range.end.line) {
+        range.end.line -= 1;
+        // LSP characters are UTF-16 code units
+        range.end.character = lines[range.end.line as usize].encode_utf16().count() as u32;
+    }
+    Some(range)
+}
+
 fn start_end_nodes_from_range<'b>(
     root_node: Node<'b>,
     mut range: Range,
     file: &str,
 ) -> Option<(Node<'b>, Node<'b>)> {
-    if range.start.character == 0 && range.end.character == 0 {
+    if range.start.character == 0 && range.end.character == 0 && range.end.line > 0 {
         range.end.line -= 1;
     }
+    let range = trim_range_to_statements(range, file)?;
     let start_node = node_from_position(root_node, range.start, file)?;
     let end_node = node_from_position(root_node, range.end, file)?;
     Some((start_node, end_node))
 }
 
-fn nodes_from_range<'b>(root_node: Node<'b>, range: Range, file: &str) -> Option<Vec<Node<'b>>> {
+pub(crate) fn nodes_from_range<'b>(
+    root_node: Node<'b>,
+    range: Range,
+    file: &str,
+) -> Option<Vec<Node<'b>>> {
     let (start_node, end_node) = start_end_nodes_from_range(root_node, range, file)?;
     if start_node.parent() != end_node.parent() {
         return None;
@@ -246,20 +405,31 @@ fn nodes_from_range<'b>(root_node: Node<'b>, range: Range, file: &str) -> Option
 
 #[cfg(test)]
 mod tests {
-    use async_lsp::lsp_types::{Position, Range};
+    use async_lsp::lsp_types::*;
+    use tree_sitter::Point;
 
     use crate::{
+        config::{Config, ExtractInsertPosition},
         extract_into_function::{
             collect_non_declared_variables, collect_top_level_variable_definitions,
+            extract_into_function_action, find_insert_position, make_function_name,
             nodes_from_range, start_end_nodes_from_range,
         },
-        utils::node_content,
+        test_utils::{apply_action, code_action_params, test_filedb},
+        utils::{node_content, parse_file},
     };
 
     use super::{
-        collect_used_variables, collect_variable_definitions, node_from_position, parse_file,
+        collect_used_variables, collect_variable_definitions, node_from_position,
+        trim_range_to_statements,
     };
 
+    fn extract(file: &str, range: Range) -> Option<String> {
+        let params = code_action_params(range);
+        let action = extract_into_function_action(&params, &Config::default(), &test_filedb(file));
+        apply_action(file, action.as_ref())
+    }
+
     #[test]
     fn test_node_from_position() {
         let file = "func foo():
@@ -349,6 +519,24 @@ var top_level_d = 10";
         );
     }
 
+    #[test]
+    fn top_level_definitions_between_comments() {
+        let file = "# Player movement
+@tool
+extends Node
+const SPEED = 10
+var velocity = 0
+
+func foo():
+\tvar a = 10
+# trailing comment";
+        let tree = parse_file(file).unwrap();
+        let variables = collect_top_level_variable_definitions(&tree, file);
+        assert_eq!(variables, vec!["SPEED", "velocity"]);
+        let tree = parse_file("").unwrap();
+        assert!(collect_top_level_variable_definitions(&tree, "").is_empty());
+    }
+
     #[test]
     fn test_collect_used_variables() {
         let file = "func foo():
@@ -377,4 +565,185 @@ func foo():
         used_variables.sort();
         assert_eq!(used_variables, vec!["d", "transform"]);
     }
+
+    #[test]
+    fn augmented_assignment_target_is_used() {
+        let file = "func foo():
+\tvar x = 0
+\tvar score = 10
+\tx += 1
+\tscore -= x";
+        let tree = parse_file(file).unwrap();
+        let start_node = node_from_position(tree.root_node(), Position::new(3, 1), file).unwrap();
+        let end_node = node_from_position(tree.root_node(), Position::new(4, 1), file).unwrap();
+        let mut used_variables = collect_non_declared_variables(&tree, start_node, end_node, file)
+            .into_iter()
+            .collect::<Vec<_>>();
+        used_variables.sort();
+        assert_eq!(used_variables, vec!["score", "x"]);
+    }
+
+    #[test]
+    fn test_make_function_name() {
+        let file = "func fun_name():
+\tpass
+
+func _extracted_1():
+\tpass";
+        let tree = parse_file(file).unwrap();
+        assert_eq!(make_function_name("fun_name", &tree, file), "fun_name2");
+        assert_eq!(
+            make_function_name("_extracted_%d", &tree, file),
+            "_extracted_2"
+        );
+        assert_eq!(make_function_name("helper", &tree, file), "helper");
+    }
+
+    #[test]
+    fn test_find_insert_position() {
+        let file = "func foo():
+\tvar a = 10
+\tprint(a)
+
+func bar():
+\tpass
+";
+        let tree = parse_file(file).unwrap();
+        let start_node = node_from_position(tree.root_node(), Position::new(1, 1), file).unwrap();
+        assert_eq!(
+            find_insert_position(start_node, ExtractInsertPosition::AfterEnclosingFunction),
+            Some(Point::new(2, 9))
+        );
+        assert_eq!(
+            find_insert_position(start_node, ExtractInsertPosition::EndOfClass),
+            Some(Point::new(5, 5))
+        );
+    }
+
+    #[test]
+    fn node_from_position_past_line_end() {
+        let file = "func foo():
+    var a = 10
+    print(a)
+";
+        let tree = parse_file(file).unwrap();
+        let node = node_from_position(tree.root_node(), Position::new(1, 200), file).unwrap();
+        assert_eq!(node_content(&node, file), "var a = 10");
+        let node = node_from_position(tree.root_node(), Position::new(2, 0), file).unwrap();
+        assert_eq!(node_content(&node, file), "print(a)");
+        assert_eq!(
+            node_from_position(tree.root_node(), Position::new(3, 40), file),
+            None
+        );
+    }
+
+    #[test]
+    fn selection_edges_on_blank_and_comment_lines() {
+        let file = "func foo():
+\tvar a = 10
+
+\t# print it
+\tprint(a)
+
+\treturn a";
+        let tree = parse_file(file).unwrap();
+        let range = Range::new(Position::new(2, 0), Position::new(5, 0));
+        let (start_node, end_node) =
+            start_end_nodes_from_range(tree.root_node(), range, file).unwrap();
+        assert_eq!(node_content(&start_node, file), "print(a)");
+        assert_eq!(node_content(&end_node, file), "print(a)");
+
+        let range = Range::new(Position::new(1, 1), Position::new(5, 1));
+        let nodes = nodes_from_range(tree.root_node(), range, file).unwrap();
+        assert_eq!(node_content(nodes.last().unwrap(), file), "print(a)");
+    }
+
+    #[test]
+    fn nothing_to_extract_from_empty_files() {
+        let files = [
+            "",
+            "  \n\t\n",
+            "# only a comment\n# and another\n",
+            "var callback = func():\n\tprint(1)\n\tprint(2)\n",
+        ];
+        for file in files {
+            let params = code_action_params(Range::new(Position::new(0, 0), Position::new(2, 9)));
+            let action =
+                extract_into_function_action(&params, &Config::default(), &test_filedb(file));
+            assert!(action.is_none(), "file {file:?}");
+        }
+    }
+
+    #[test]
+    fn extract_moves_comments_and_keeps_separator_style() {
+        let file = "func foo():
+\tvar a = 10
+\t# doubled value
+\tvar b = a * 2
+\tprint(b) # show it
+\treturn a
+
+func bar():
+\tpass
+";
+        assert_eq!(
+            extract(file, Range::new(Position::new(2, 1), Position::new(4, 9))).unwrap(),
+            "func foo():
+\tvar a = 10
+\tfun_name(a)
+\treturn a
+
+func fun_name(a):
+\t# doubled value
+\tvar b = a * 2
+\tprint(b) # show it
+
+func bar():
+\tpass
+"
+        );
+    }
+
+    #[test]
+    fn extract_keeps_keyword_statements() {
+        let file = "func foo(a):
+\tif a:
+\t\tpass
+\tbreakpoint
+\tprint(a)
+\treturn a
+
+func bar():
+\tpass
+";
+        assert_eq!(
+            extract(file, Range::new(Position::new(1, 1), Position::new(4, 9))).unwrap(),
+            "func foo(a):
+\tfun_name(a)
+\treturn a
+
+func fun_name(a):
+\tif a:
+\t\tpass
+\tbreakpoint
+\tprint(a)
+
+func bar():
+\tpass
+"
+        );
+    }
+
+    #[test]
+    fn trimmed_end_counts_utf16_code_units() {
+        let file = "func foo():
+\tprint(\"héllo\")
+
+";
+        let range = Range::new(Position::new(1, 1), Position::new(2, 0));
+        assert_eq!(
+            trim_range_to_statements(range, file),
+            Some(Range::new(Position::new(1, 1), Position::new(1, 15)))
+        );
+    }
 }
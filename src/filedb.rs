@@ -1,24 +1,33 @@
 use std::{collections::HashMap, sync::Arc};
 
-use async_lsp::lsp_types::TextDocumentContentChangeEvent;
+use async_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
 use parking_lot::RwLock;
 use ropey::{LineType, Rope};
 use tree_sitter::{Point, Tree};
 
-use crate::utils::{parse_file, position_to_point, reparse_file};
+use crate::utils::{ParseError, clamp_range, position_to_byte, reparse_file, try_parse_file};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct FileDatabase {
     pub(crate) files: Arc<RwLock<HashMap<String, SourceFile>>>,
 }
 
 impl FileDatabase {
-    pub fn file_opened(&self, file_path: &str, file_content: String) {
-        let s = file_content;
-        let Some(tree) = parse_file(&s) else {
-            return;
-        };
-        let rope = Rope::from(s);
+    pub fn file_opened(&self, file_path: &str, file_content: String) -> Result<(), ParseError> {
+        let parsed = try_parse_file(&file_content);
+        self.insert_file(file_path, file_content, parsed)
+    }
+
+    fn insert_file(
+        &self,
+        file_path: &str,
+        file_content: String,
+        parsed: Result<Tree, ParseError>,
+    ) -> Result<(), ParseError> {
+        let tree = parsed.inspect_err(|error| {
+            tracing::warn!("File {file_path} couldn't be parsed: {error}");
+        })?;
+        let rope = Rope::from(file_content);
         self.files.write().insert(
             file_path.to_string(),
             SourceFile {
@@ -26,6 +35,7 @@ impl FileDatabase {
                 tree,
             },
         );
+        Ok(())
     }
 
     pub fn file_changed(
@@ -41,51 +51,39 @@ impl FileDatabase {
             let Some(range) = change.range else {
                 continue;
             };
+            let range = clamp_range(&file.content, range);
 
-            let start_line_byte_idx = file
-                .content
-                .line_to_byte_idx(range.start.line as usize, LineType::LF_CR);
-            let start_line_char_idx = file.content.byte_to_char_idx(start_line_byte_idx);
-            let start_char_idx = start_line_char_idx + range.start.character as usize;
-            let start = file.content.char_to_byte_idx(start_char_idx);
-
-            let end_line_byte_idx = file
-                .content
-                .line_to_byte_idx(range.end.line as usize, LineType::LF_CR);
-            let end_line_char_idx = file.content.byte_to_char_idx(end_line_byte_idx);
-            let end_char_idx = end_line_char_idx + range.end.character as usize;
-            let end = file.content.char_to_byte_idx(end_char_idx);
+            let (start_line_byte_idx, start) = line_and_position_bytes(&file.content, range.start);
+            let (end_line_byte_idx, end) = line_and_position_bytes(&file.content, range.end);
 
             file.content.remove(start..end);
             if !change.text.is_empty() {
                 file.content.insert(start, &change.text);
             }
 
-            let new_line_breaks = change.text.chars().filter(|c| c == &'\n').count();
-            let mut trailing_characters = 0;
-            for ch in change.text.chars().rev() {
-                if ch == '\n' {
-                    break;
-                }
-                trailing_characters += 1;
-            }
+            // tree-sitter columns are bytes, text without line breaks continues the start line
+            let start_column = start - start_line_byte_idx;
+            let end_column = end - end_line_byte_idx;
+            let new_line_breaks = change.text.matches('\n').count();
+            let trailing_bytes = match change.text.rfind('\n') {
+                Some(index) => change.text.len() - index - 1,
+                None => start_column + change.text.len(),
+            };
 
             let new_end_byte = start + change.text.len();
-            let new_end_position = Point::new(
-                range.start.line as usize + new_line_breaks,
-                trailing_characters,
-            );
+            let new_end_position =
+                Point::new(range.start.line as usize + new_line_breaks, trailing_bytes);
 
             file.tree.edit(&tree_sitter::InputEdit {
                 start_byte: start,
                 old_end_byte: end,
                 new_end_byte,
-                start_position: position_to_point(range.start),
-                old_end_position: position_to_point(range.end),
+                start_position: Point::new(range.start.line as usize, start_column),
+                old_end_position: Point::new(range.end.line as usize, end_column),
                 new_end_position,
             });
         }
-        if let Some(new_tree) = reparse_file(&file.content.to_string(), &file.tree) {
+        if let Some(new_tree) = reparse_file(&file.content, &file.tree) {
             file.tree = new_tree
         }
     }
@@ -95,3 +93,167 @@ pub struct SourceFile {
     pub(crate) content: Rope,
     pub(crate) tree: Tree,
 }
+
+/// Returns byte offsets of the line start and of the position, the character counts
+/// UTF-16 code units as LSP positions do
+fn line_and_position_bytes(content: &Rope, position: Position) -> (usize, usize) {
+    let line_start = content.line_to_byte_idx(position.line as usize, LineType::LF_CR);
+    let line = content
+        .line(position.line as usize, LineType::LF_CR)
+        .to_string();
+    let column = position_to_byte(&line, Position::new(0, position.character));
+    (line_start, line_start + column)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use async_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use crate::{
+        symbol_table::SymbolTable,
+        typedb::TypeDatabase,
+        utils::{ParseError, parse_file, walk_tree},
+    };
+
+    use super::FileDatabase;
+
+    #[derive(Clone, Default)]
+    struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for LogBuffer {
+        type Writer = LogBuffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn unparseable_file_logs_warning() {
+        let logs = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .finish();
+        let filedb = FileDatabase::default();
+        let result = tracing::subscriber::with_default(subscriber, || {
+            filedb.insert_file("/broken.gd", String::new(), Err(ParseError::NoTree))
+        });
+        assert!(result.is_err());
+        assert!(filedb.files.read().is_empty());
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("/broken.gd"));
+    }
+
+    #[test]
+    fn change_positions_count_utf16_code_units() {
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/test.gd", "var a = \"😀\"\nvar b".to_string())
+            .unwrap();
+        // `😀` is 2 UTF-16 code units, the closing quote is at character 11
+        filedb.file_changed(
+            "/test.gd",
+            vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 11), Position::new(0, 11))),
+                range_length: None,
+                text: "!".to_string(),
+            }],
+        );
+        let lock = filedb.files.read();
+        assert_eq!(
+            lock["/test.gd"].content.to_string(),
+            "var a = \"😀!\"\nvar b"
+        );
+    }
+
+    #[test]
+    fn out_of_range_change_is_clamped() {
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/test.gd", "var a = 1\nvar b = 2".to_string())
+            .unwrap();
+        filedb.file_changed(
+            "/test.gd",
+            vec![TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(1, 40), Position::new(100, 5))),
+                range_length: None,
+                text: "\nvar c = 3".to_string(),
+            }],
+        );
+        let lock = filedb.files.read();
+        assert_eq!(
+            lock["/test.gd"].content.to_string(),
+            "var a = 1\nvar b = 2\nvar c = 3"
+        );
+    }
+
+    #[test]
+    fn edits_around_multiline_string_keep_positions() {
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened(
+                "/test.gd",
+                "var text = \"\"\"first\nsecond\"\"\"\nvar a = 1\nvar b = 2".to_string(),
+            )
+            .unwrap();
+        let change = |start: Position, end: Position, text: &str| TextDocumentContentChangeEvent {
+            range: Some(Range::new(start, end)),
+            range_length: None,
+            text: text.to_string(),
+        };
+        filedb.file_changed(
+            "/test.gd",
+            vec![
+                // rename `a` on the line after the string
+                change(Position::new(2, 4), Position::new(2, 5), "speed"),
+                // break the string into one more line
+                change(Position::new(0, 19), Position::new(0, 19), "\nmiddle"),
+            ],
+        );
+
+        let lock = filedb.files.read();
+        let content = lock["/test.gd"].content.to_string();
+        assert_eq!(
+            content,
+            "var text = \"\"\"first\nmiddle\nsecond\"\"\"\nvar speed = 1\nvar b = 2"
+        );
+        let nodes = |tree: &tree_sitter::Tree| {
+            let mut nodes = Vec::new();
+            walk_tree(tree.root_node(), &mut |node| {
+                nodes.push((node.kind(), node.start_position(), node.end_position()))
+            });
+            nodes
+        };
+        assert_eq!(
+            nodes(&lock["/test.gd"].tree),
+            nodes(&parse_file(&content).unwrap())
+        );
+
+        let typedb = TypeDatabase::from_file("./assets/type_info.json").unwrap();
+        let mut st = SymbolTable::new(&typedb);
+        st.build_table(&lock["/test.gd"].tree, &content);
+        let root_scope = &st.map[&st.root_scope()];
+        let b = root_scope.vars.iter().find(|var| var.name == "b").unwrap();
+        assert_eq!(b.hint_position, Position::new(4, 5));
+    }
+}
@@ -0,0 +1,390 @@
+use async_lsp::lsp_types::*;
+use tree_sitter::{Node, Point};
+
+use crate::{
+    cancellation::CancellationToken,
+    filedb::FileDatabase,
+    utils::{byte_to_utf16_column, walk_tree},
+};
+
+/// Re-indents lines in the range according to their depth in the tree with tabs or spaces
+/// as the options ask and strips trailing whitespace, lines continuing a multiline
+/// expression and contents of multiline strings are left as is
+pub fn format_range(
+    path: &str,
+    range: Range,
+    options: &FormattingOptions,
+    filedb: &FileDatabase,
+    cancellation: &CancellationToken,
+) -> Vec<TextEdit> {
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let root = source_file.tree.root_node();
+    let indent_unit = indent_unit(options);
+
+    // rows strings continue to or end on, their whitespace is a part of the string value
+    let mut string_rows = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.kind() == "string" && node.start_position().row != node.end_position().row {
+            string_rows.push(node.start_position().row..node.end_position().row + 1);
+        }
+    });
+    let string_starts_before = |row: usize| {
+        string_rows
+            .iter()
+            .any(|rows| rows.start < row && rows.contains(&row))
+    };
+    let string_continues_after = |row: usize| {
+        string_rows
+            .iter()
+            .any(|rows| rows.contains(&row) && row + 1 < rows.end)
+    };
+
+    let mut edits = Vec::new();
+    let lines = file
+        .lines()
+        .enumerate()
+        .skip(range.start.line as usize)
+        .take(range.end.line.saturating_sub(range.start.line) as usize + 1);
+    for (row, line) in lines {
+        if cancellation.is_cancelled() {
+            return Vec::new();
+        }
+        let string_at_start = string_starts_before(row);
+        let string_at_end = string_continues_after(row);
+        let content = line.trim();
+        let indent_len = line.len() - line.trim_start().len();
+        let trimmed_end = line.trim_end().len();
+        if content.is_empty() {
+            if !line.is_empty() && !string_at_start {
+                edits.push(replace_columns(row, line, 0, line.len(), ""));
+            }
+            continue;
+        }
+        if trimmed_end < line.len() && !string_at_end {
+            edits.push(replace_columns(row, line, trimmed_end, line.len(), ""));
+        }
+        if string_at_start {
+            continue;
+        }
+        let Some(depth) = statement_depth(root, Point::new(row, indent_len)) else {
+            continue;
+        };
+        let indent = indent_unit.repeat(depth);
+        if line[..indent_len] != indent {
+            edits.push(replace_columns(row, line, 0, indent_len, &indent));
+        }
+    }
+    edits
+}
+
+/// One level of indentation with tabs or spaces as the options ask
+fn indent_unit(options: &FormattingOptions) -> String {
+    if options.insert_spaces {
+        " ".repeat(options.tab_size as usize)
+    } else {
+        "\t".to_string()
+    }
+}
+
+/// Indents the line the cursor moved to after a newline one level deeper than a previous
+/// block header ending with `:`, a typed `:` of `else`/`elif` moves the clause back
+/// to the indent of its `if`
+pub fn format_on_type(
+    path: &str,
+    position: Position,
+    ch: &str,
+    options: &FormattingOptions,
+    filedb: &FileDatabase,
+) -> Vec<TextEdit> {
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(path) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let root = source_file.tree.root_node();
+    let lines = file.split('\n').collect::<Vec<_>>();
+    let row = position.line as usize;
+    let Some(line) = lines.get(row) else {
+        return Vec::new();
+    };
+    let indent_len = line.len() - line.trim_start().len();
+
+    let indent = match ch {
+        "\n" => {
+            let Some((header_row, header)) = lines[..row]
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(_, line)| !line.trim().is_empty())
+            else {
+                return Vec::new();
+            };
+            let header_indent = &header[..header.len() - header.trim_start().len()];
+            if code_before_comment(root, header_row, header).ends_with(':') {
+                format!("{header_indent}{}", indent_unit(options))
+            } else {
+                header_indent.to_string()
+            }
+        }
+        ":" => {
+            let content = line.trim_start();
+            if !(content.starts_with("else") || content.starts_with("elif ")) {
+                return Vec::new();
+            }
+            let Some(if_line) = lines[..row].iter().rev().find(|previous| {
+                let previous_indent = previous.len() - previous.trim_start().len();
+                let previous = previous.trim_start();
+                previous_indent < indent_len
+                    && (previous.starts_with("if ") || previous.starts_with("elif "))
+            }) else {
+                return Vec::new();
+            };
+            if_line[..if_line.len() - if_line.trim_start().len()].to_string()
+        }
+        _ => return Vec::new(),
+    };
+    if line[..indent_len] == indent {
+        return Vec::new();
+    }
+    vec![replace_columns(row, line, 0, indent_len, &indent)]
+}
+
+/// Returns the line without a trailing comment and whitespace, `#` inside strings is kept
+fn code_before_comment<'l>(root: Node, row: usize, line: &'l str) -> &'l str {
+    let trimmed = line.trim_end();
+    let Some(last_column) = trimmed.len().checked_sub(1) else {
+        return trimmed;
+    };
+    let point = Point::new(row, last_column);
+    match root.descendant_for_point_range(point, point) {
+        Some(node) if node.kind() == "comment" && node.start_position().row == row => {
+            trimmed[..node.start_position().column].trim_end()
+        }
+        _ => trimmed,
+    }
+}
+
+/// Replaces text of the line between byte columns, the edit range counts UTF-16 code units
+fn replace_columns(row: usize, line: &str, start: usize, end: usize, new_text: &str) -> TextEdit {
+    TextEdit::new(
+        Range::new(
+            Position::new(row as u32, byte_to_utf16_column(line, start)),
+            Position::new(row as u32, byte_to_utf16_column(line, end)),
+        ),
+        new_text.to_string(),
+    )
+}
+
+/// Returns number of blocks enclosing the statement starting at the point,
+/// `None` if no statement starts there
+fn statement_depth(root: Node, point: Point) -> Option<usize> {
+    let mut node = root.descendant_for_point_range(point, point)?;
+    if node.kind() == "comment" {
+        return None;
+    }
+    while let Some(parent) = node.parent() {
+        if parent.start_byte() != node.start_byte() || is_block(parent) {
+            break;
+        }
+        node = parent;
+    }
+    let starts_line = node.start_position() == point
+        && node.parent().is_some_and(|parent| {
+            is_block(parent) || matches!(node.kind(), "elif_clause" | "else_clause")
+        });
+    if !starts_line {
+        return None;
+    }
+
+    let mut depth = 0;
+    let mut ancestor = node.parent();
+    while let Some(cur_node) = ancestor {
+        if matches!(cur_node.kind(), "body" | "match_body" | "class_body") {
+            depth += 1;
+        }
+        ancestor = cur_node.parent();
+    }
+    Some(depth)
+}
+
+fn is_block(node: Node) -> bool {
+    matches!(node.kind(), "source" | "body" | "match_body" | "class_body")
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{FormattingOptions, Position, Range};
+
+    use crate::{
+        cancellation::CancellationToken,
+        test_utils::{TEST_PATH, apply_edits, test_filedb},
+    };
+
+    use super::{format_on_type, format_range};
+
+    fn apply(file: &str, range: Range) -> String {
+        apply_with_options(file, range, &FormattingOptions::default())
+    }
+
+    fn apply_with_options(file: &str, range: Range, options: &FormattingOptions) -> String {
+        let edits = format_range(
+            TEST_PATH,
+            range,
+            options,
+            &test_filedb(file),
+            &CancellationToken::default(),
+        );
+        apply_edits(file, edits)
+    }
+
+    #[test]
+    fn reindent_only_requested_block() {
+        let file = "func foo(a):
+    if a:
+        print(a)
+    return a
+
+func bar():
+    pass";
+        let range = Range::new(Position::new(0, 0), Position::new(3, 12));
+        assert_eq!(
+            apply(file, range),
+            "func foo(a):
+\tif a:
+\t\tprint(a)
+\treturn a
+
+func bar():
+    pass"
+        );
+    }
+
+    #[test]
+    fn multiline_string_contents_are_kept() {
+        // trailing spaces are written as escapes so editors don't strip them from the test
+        let file = "func foo():\n\tvar text = \"\"\"first\x20\x20\n\x20\x20\n  second\"\"\"\x20\n\treturn text";
+        let range = Range::new(Position::new(0, 0), Position::new(4, 12));
+        assert_eq!(
+            apply(file, range),
+            "func foo():\n\tvar text = \"\"\"first\x20\x20\n\x20\x20\n  second\"\"\"\n\treturn text"
+        );
+    }
+
+    #[test]
+    fn reindent_with_spaces_from_options() {
+        let file = "func foo(a):
+\tif a:
+\t\tprint(a)";
+        let options = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        let range = Range::new(Position::new(0, 0), Position::new(2, 10));
+        assert_eq!(
+            apply_with_options(file, range, &options),
+            "func foo(a):
+    if a:
+        print(a)"
+        );
+    }
+
+    #[test]
+    fn trailing_whitespace_after_multibyte_text() {
+        let file = "func foo():
+\tprint(\"héllo\")  ";
+        let range = Range::new(Position::new(0, 0), Position::new(1, 20));
+        let edits = format_range(
+            TEST_PATH,
+            range,
+            &FormattingOptions::default(),
+            &test_filedb(file),
+            &CancellationToken::default(),
+        );
+        // `é` takes two bytes but one UTF-16 code unit
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(1, 15), Position::new(1, 17))
+        );
+    }
+
+    #[test]
+    fn newline_after_block_header_is_indented() {
+        let filedb = test_filedb("func foo():\n");
+        let edits = format_on_type(
+            TEST_PATH,
+            Position::new(1, 0),
+            "\n",
+            &FormattingOptions::default(),
+            &filedb,
+        );
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 0))
+        );
+        assert_eq!(edits[0].new_text, "\t");
+
+        let file = "func foo():
+\tif ready: # wait
+\t";
+        filedb.file_opened(TEST_PATH, file.to_string()).unwrap();
+        let edits = format_on_type(
+            TEST_PATH,
+            Position::new(2, 1),
+            "\n",
+            &FormattingOptions::default(),
+            &filedb,
+        );
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(2, 0), Position::new(2, 1))
+        );
+        assert_eq!(edits[0].new_text, "\t\t");
+
+        let file = "func foo():
+\tif true:
+\t\tpass
+\t\telse:";
+        filedb.file_opened(TEST_PATH, file.to_string()).unwrap();
+        let edits = format_on_type(
+            TEST_PATH,
+            Position::new(3, 7),
+            ":",
+            &FormattingOptions::default(),
+            &filedb,
+        );
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(3, 0), Position::new(3, 2))
+        );
+        assert_eq!(edits[0].new_text, "\t");
+    }
+
+    #[test]
+    fn newline_is_indented_with_spaces_from_options() {
+        let file = "func foo():
+    if ready:
+    ";
+        let options = FormattingOptions {
+            tab_size: 4,
+            insert_spaces: true,
+            ..Default::default()
+        };
+        let edits = format_on_type(
+            TEST_PATH,
+            Position::new(2, 4),
+            "\n",
+            &options,
+            &test_filedb(file),
+        );
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(2, 0), Position::new(2, 4))
+        );
+        assert_eq!(edits[0].new_text, "        ");
+    }
+}
@@ -0,0 +1,294 @@
+use async_lsp::lsp_types::*;
+
+use tree_sitter::{Node, Tree};
+
+use crate::{
+    filedb::FileDatabase,
+    symbol_table::SymbolTable,
+    typedb::{SymbolType, TypeDatabase},
+    utils::{
+        annotation_arguments, annotation_name, declaration_annotations, doc_comment,
+        function_header, identifier_at, markdown_to_plain_text, node_content, node_to_range,
+    },
+};
+
+pub fn make_hover(
+    path: &str,
+    position: Position,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    markdown: bool,
+) -> Option<Hover> {
+    let lock = filedb.files.read();
+    let source_file = lock.get(path)?;
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let node = identifier_at(tree.root_node(), position)?;
+    let name = node_content(&node, &file);
+    let ttype = SymbolType::from_str(name);
+    let value = if typedb.classes.contains_key(&ttype) {
+        class_summary(&ttype, typedb)
+    } else if let Some(summary) = function_summary(node, tree, &file) {
+        summary
+    } else {
+        variable_summary(position, tree, &file, typedb)?
+    };
+    let contents = if markdown {
+        MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }
+    } else {
+        MarkupContent {
+            kind: MarkupKind::PlainText,
+            value: markdown_to_plain_text(&value),
+        }
+    };
+    Some(Hover {
+        contents: HoverContents::Markup(contents),
+        range: Some(node_to_range(&node)),
+    })
+}
+
+/// Renders header and doc comment of the function defined in the file
+/// if the identifier is its name or a call to it
+fn function_summary(node: Node, tree: &Tree, file: &str) -> Option<String> {
+    let parent = node.parent()?;
+    let is_function_name = match parent.kind() {
+        "function_definition" => parent.child_by_field_name("name") == Some(node),
+        "call" => parent.child(0) == Some(node),
+        _ => false,
+    };
+    if !is_function_name {
+        return None;
+    }
+    let name = node_content(&node, file);
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let function = root.children(&mut cursor).find(|child| {
+        child.kind() == "function_definition"
+            && child
+                .child_by_field_name("name")
+                .is_some_and(|name_node| node_content(&name_node, file) == name)
+    })?;
+    let mut summary = format!("```gdscript\n{}\n```", function_header(function, file));
+    if let Some(doc) = doc_comment(function, file) {
+        summary += &format!("\n\n{doc}");
+    }
+    Some(summary)
+}
+
+/// Renders declaration of the variable referenced by the identifier at the position
+/// together with the constraints of its export annotation
+fn variable_summary(
+    position: Position,
+    tree: &Tree,
+    file: &str,
+    typedb: &TypeDatabase,
+) -> Option<String> {
+    let mut st = SymbolTable::new(typedb);
+    st.build_table(tree, file);
+    let symbol = st.symbol_at(tree.root_node(), position, file)?;
+    let declaration = identifier_at(tree.root_node(), symbol.hint_position)?.parent()?;
+
+    let keyword = if declaration.kind() == "const_statement" {
+        "const"
+    } else {
+        "var"
+    };
+    let ttype = symbol
+        .ttype
+        .as_ref()
+        .map_or("Variant".to_string(), |ttype| ttype.to_string());
+    let mut summary = format!("```gdscript\n{keyword} {}: {ttype}\n```", symbol.name);
+    if let Some(export) = export_summary(declaration, &ttype, file) {
+        summary += &format!("\n\n{export}");
+    }
+    if let Some(doc) = doc_comment(declaration, file) {
+        summary += &format!("\n\n{doc}");
+    }
+    // the ancestry of the type is secondary, so it goes below a separator after the docs
+    if let Some(class) = &symbol.ttype
+        && let Some(chain) = inheritance_chain(class, typedb)
+    {
+        summary += &format!("\n\n---\n\n{} → {chain}", class.to_string());
+    }
+    Some(summary)
+}
+
+/// Describes `@export`, `@export_range`, `@export_enum` and `@export_flags` annotations
+/// of a declaration,
+/// e.g. "exported float, range 0..100"
+fn export_summary(declaration: Node, ttype: &str, file: &str) -> Option<String> {
+    let annotation = declaration_annotations(declaration)
+        .into_iter()
+        .find(|annotation| annotation_name(*annotation, file).starts_with("export"))?;
+    let arguments = annotation_arguments(annotation, file);
+    let mut summary = format!("exported {ttype}");
+    match annotation_name(annotation, file) {
+        "export_range" if arguments.len() >= 2 => {
+            summary += &format!(", range {}..{}", arguments[0], arguments[1]);
+            if let Some(step) = arguments.get(2) {
+                summary += &format!(", step {step}");
+            }
+        }
+        "export_enum" if !arguments.is_empty() => {
+            let options = arguments
+                .iter()
+                .map(|option| option.trim_matches('"'))
+                .collect::<Vec<_>>();
+            summary += &format!(", one of {}", options.join(", "));
+        }
+        "export_flags" if !arguments.is_empty() => {
+            let flags = arguments
+                .iter()
+                .map(|flag| flag.trim_matches('"'))
+                .collect::<Vec<_>>();
+            summary += &format!(", flags {}", flags.join(", "));
+        }
+        _ => (),
+    }
+    Some(summary)
+}
+
+/// Renders class declaration with its ancestry, e.g.
+/// `CharacterBody3D extends PhysicsBody3D → CollisionObject3D → Node3D → Node → Object`
+fn class_summary(class: &SymbolType, typedb: &TypeDatabase) -> String {
+    let mut summary = format!("```gdscript\nclass {}\n```", class.to_string());
+    if let SymbolType::Variant(_) = class {
+        summary += "\nBuilt-in Variant type";
+    }
+    if let Some(chain) = inheritance_chain(class, typedb) {
+        summary += &format!("\n\nextends {chain}");
+    }
+    summary
+}
+
+/// Joins ancestors of the class from the direct parent up to `Object`,
+/// `None` for classes without parents
+fn inheritance_chain(class: &SymbolType, typedb: &TypeDatabase) -> Option<String> {
+    let chain = typedb.inheritance_chain(class);
+    if chain.is_empty() {
+        return None;
+    }
+    Some(
+        chain
+            .iter()
+            .map(|class| class.to_string())
+            .collect::<Vec<_>>()
+            .join(" → "),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{HoverContents, MarkupKind, Position};
+
+    use crate::{filedb::FileDatabase, test_utils::TEST_TYPEDB};
+
+    use super::make_hover;
+
+    fn hover_text(file: &str, position: Position) -> Option<String> {
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let hover = make_hover("/test.gd", position, &TEST_TYPEDB, &filedb, true)?;
+        let HoverContents::Markup(content) = hover.contents else {
+            return None;
+        };
+        Some(content.value)
+    }
+
+    #[test]
+    fn hover_builtin_class_shows_parents() {
+        let text = hover_text("extends CharacterBody3D", Position::new(0, 10)).unwrap();
+        assert!(text.contains("class CharacterBody3D"));
+        assert!(text.contains("extends PhysicsBody3D → CollisionObject3D"));
+    }
+
+    #[test]
+    fn hover_variant_type() {
+        let file = "func foo():
+\tvar v = Vector3.ZERO";
+        let text = hover_text(file, Position::new(1, 11)).unwrap();
+        assert!(text.contains("Built-in Variant type"));
+    }
+
+    #[test]
+    fn hover_export_range_variable() {
+        let file = "@export_range(0, 100) var health: float = 50.0
+func foo():
+\tprint(health)";
+        let text = hover_text(file, Position::new(2, 8)).unwrap();
+        assert!(text.contains("var health: float"));
+        assert!(text.contains("exported float, range 0..100"));
+    }
+
+    #[test]
+    fn hover_export_enum_variable() {
+        let file = "@export_enum(\"Warrior\", \"Mage\")
+var class_type: int";
+        let text = hover_text(file, Position::new(1, 6)).unwrap();
+        assert!(text.contains("exported int, one of Warrior, Mage"));
+    }
+
+    #[test]
+    fn hover_export_flags_variable() {
+        let file = "@export_flags(\"Fire\", \"Water\", \"Earth\")
+var elements";
+        let text = hover_text(file, Position::new(1, 6)).unwrap();
+        assert!(text.starts_with("```gdscript\nvar elements: int\n```"));
+        assert!(text.contains("exported int, flags Fire, Water, Earth"));
+    }
+
+    #[test]
+    fn plain_text_hover_without_markdown_support() {
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/test.gd", "var count: int".to_string())
+            .unwrap();
+        let hover = make_hover(
+            "/test.gd",
+            Position::new(0, 6),
+            &TEST_TYPEDB,
+            &filedb,
+            false,
+        );
+        let HoverContents::Markup(content) = hover.unwrap().contents else {
+            panic!("expected markup content");
+        };
+        assert_eq!(content.kind, MarkupKind::PlainText);
+        assert_eq!(content.value, "var count: int");
+    }
+
+    #[test]
+    fn hover_variable_shows_type_ancestry() {
+        let file = "var body: CharacterBody3D
+func foo():
+\tvar count = 1
+\tprint(body, count)";
+        let text = hover_text(file, Position::new(3, 8)).unwrap();
+        assert!(text.starts_with("```gdscript\nvar body: CharacterBody3D\n```"));
+        assert!(text.ends_with(
+            "---\n\nCharacterBody3D → PhysicsBody3D → CollisionObject3D → Node3D → Node → Object"
+        ));
+
+        let text = hover_text(file, Position::new(3, 14)).unwrap();
+        assert!(!text.contains("→"));
+    }
+
+    #[test]
+    fn hover_function_shows_doc_comment() {
+        let file = "extends Node
+## Heals the player.
+## Amount is clamped to max health.
+func heal(amount: int) -> void:
+\tpass
+
+func _ready():
+\theal(10)";
+        let text = hover_text(file, Position::new(7, 2)).unwrap();
+        assert!(text.contains("func heal(amount: int) -> void\n"));
+        assert!(text.ends_with("Heals the player.\nAmount is clamped to max health."));
+    }
+}
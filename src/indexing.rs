@@ -0,0 +1,202 @@
+use std::path::{Path, PathBuf};
+
+use async_lsp::lsp_types::{
+    DidChangeWatchedFilesRegistrationOptions, FileChangeType, FileEvent, FileSystemWatcher,
+    GlobPattern, Registration, notification::Notification,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    classdb::ClassDatabase, filedb::FileDatabase, scenedb::SceneDatabase, utils::uri_to_path,
+};
+
+/// Sent once the initial workspace scan is finished and workspace features are reliable
+pub enum IndexingComplete {}
+
+impl Notification for IndexingComplete {
+    type Params = IndexingCompleteParams;
+    const METHOD: &'static str = "gdscript/indexingComplete";
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexingCompleteParams {
+    pub scripts: usize,
+    pub classes: usize,
+    pub scenes: usize,
+}
+
+/// Scans scripts and scenes of the workspace into the databases
+pub fn index_workspace(
+    root: &Path,
+    classdb: &ClassDatabase,
+    scenedb: &SceneDatabase,
+) -> IndexingCompleteParams {
+    let scripts = classdb.scan_workspace(root);
+    let scenes = scenedb.scan_workspace(root);
+    IndexingCompleteParams {
+        scripts,
+        classes: classdb.classes.read().len(),
+        scenes,
+    }
+}
+
+/// Scans the workspace on a blocking thread and passes the counts to `notify` once done,
+/// so requests are served while a large project is being indexed
+pub async fn index_workspace_in_background(
+    root: PathBuf,
+    classdb: ClassDatabase,
+    scenedb: SceneDatabase,
+    notify: impl FnOnce(IndexingCompleteParams),
+) {
+    let scan = tokio::task::spawn_blocking(move || index_workspace(&root, &classdb, &scenedb));
+    if let Ok(params) = scan.await {
+        notify(params);
+    }
+}
+
+/// Asks the client to report scripts and scenes changed outside the editor,
+/// e.g. by a git checkout or code generation
+pub fn watched_files_registration() -> Registration {
+    let watchers = ["**/*.gd", "**/*.tscn"]
+        .into_iter()
+        .map(|pattern| FileSystemWatcher {
+            glob_pattern: GlobPattern::String(pattern.to_string()),
+            kind: None,
+        })
+        .collect();
+    Registration {
+        id: "watched-files".to_string(),
+        method: "workspace/didChangeWatchedFiles".to_string(),
+        register_options: serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
+            watchers,
+        })
+        .ok(),
+    }
+}
+
+/// Updates the databases from files changed on disk, scripts opened in the editor are
+/// kept as the client sends their content. Scenes are keyed by the attached script,
+/// so a deleted scene stays until the next workspace scan
+pub fn apply_file_events(
+    events: &[FileEvent],
+    workspace_root: &Path,
+    filedb: &FileDatabase,
+    classdb: &ClassDatabase,
+    scenedb: &SceneDatabase,
+) {
+    for event in events {
+        let path = uri_to_path(&event.uri);
+        if filedb.files.read().contains_key(&path) {
+            continue;
+        }
+        let is_deleted = event.typ == FileChangeType::DELETED;
+        if path.ends_with(".gd") {
+            if is_deleted {
+                classdb.file_removed(&path);
+            } else {
+                classdb.read_from_disk(Path::new(&path));
+            }
+        } else if path.ends_with(".tscn") && !is_deleted {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            scenedb.update_scene(workspace_root, &content);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{FileChangeType, FileEvent, Url};
+
+    use crate::{classdb::ClassDatabase, filedb::FileDatabase, scenedb::SceneDatabase};
+
+    use super::{
+        IndexingCompleteParams, apply_file_events, index_workspace, index_workspace_in_background,
+    };
+
+    #[test]
+    fn scan_reports_counts() {
+        let root = std::env::temp_dir().join(format!("sidekick-indexing-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("enemies")).unwrap();
+        std::fs::write(root.join("player.gd"), "class_name Player\nextends Node").unwrap();
+        std::fs::write(root.join("enemies/enemy.gd"), "extends Node").unwrap();
+        std::fs::write(
+            root.join("main.tscn"),
+            "[ext_resource type=\"Script\" path=\"res://player.gd\" id=\"1\"]
+[node name=\"Player\" type=\"Node\"]
+script = ExtResource(\"1\")",
+        )
+        .unwrap();
+
+        let params = index_workspace(&root, &ClassDatabase::default(), &SceneDatabase::default());
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            params,
+            IndexingCompleteParams {
+                scripts: 2,
+                classes: 1,
+                scenes: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn completion_is_notified_after_background_scan() {
+        let root = std::env::temp_dir().join(format!(
+            "sidekick-indexing-background-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("player.gd"), "class_name Player\nextends Node").unwrap();
+
+        let classdb = ClassDatabase::default();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        index_workspace_in_background(
+            root.clone(),
+            classdb.clone(),
+            SceneDatabase::default(),
+            move |params| sender.send(params).unwrap(),
+        )
+        .await;
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            receiver.try_recv(),
+            Ok(IndexingCompleteParams {
+                scripts: 1,
+                classes: 1,
+                scenes: 0,
+            })
+        );
+        assert!(classdb.classes.read().contains_key("Player"));
+    }
+
+    #[test]
+    fn watched_file_events_update_registry() {
+        let root = std::env::temp_dir().join(format!("sidekick-watched-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let script = root.join("player.gd");
+        let (filedb, classdb, scenedb) = (
+            FileDatabase::default(),
+            ClassDatabase::default(),
+            SceneDatabase::default(),
+        );
+        let apply = |typ| {
+            let event = FileEvent::new(Url::from_file_path(&script).unwrap(), typ);
+            apply_file_events(&[event], &root, &filedb, &classdb, &scenedb);
+            let mut names = classdb.classes.read().keys().cloned().collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        std::fs::write(&script, "class_name Player\nextends Node").unwrap();
+        assert_eq!(apply(FileChangeType::CREATED), vec!["Player"]);
+        std::fs::write(&script, "class_name Hero\nextends Node").unwrap();
+        assert_eq!(apply(FileChangeType::CHANGED), vec!["Hero"]);
+        std::fs::remove_file(&script).unwrap();
+        assert!(apply(FileChangeType::DELETED).is_empty());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
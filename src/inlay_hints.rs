@@ -1,7 +1,13 @@
 use async_lsp::lsp_types::*;
 
+use tree_sitter::Tree;
+
 use crate::{
-    filedb::FileDatabase, symbol_table::SymbolTable, typedb::TypeDatabase, utils::range_contains,
+    filedb::FileDatabase,
+    perf::PERF_TARGET,
+    symbol_table::SymbolTable,
+    typedb::TypeDatabase,
+    utils::{clamp_range, position_to_byte, position_to_point, range_contains},
 };
 
 pub fn make_inlay_hints(
@@ -10,6 +16,7 @@ pub fn make_inlay_hints(
     typedb: &TypeDatabase,
     filedb: &FileDatabase,
 ) -> Vec<InlayHint> {
+    let _span = tracing::debug_span!(target: PERF_TARGET, "make_inlay_hints").entered();
     let mut hints = Vec::new();
     let lock = filedb.files.read();
     let Some(source_file) = lock.get(path) else {
@@ -17,13 +24,24 @@ pub fn make_inlay_hints(
     };
     let file = source_file.content.to_string();
     let tree = &source_file.tree;
+    let range = clamp_range(&source_file.content, range);
+    // positions in a tree that failed to parse as a whole can't be trusted,
+    // it's better to show no hints than misplaced ones
+    if tree.root_node().is_error() {
+        return hints;
+    }
 
+    // functions outside of the visible range aren't analyzed at all,
+    // so a small range of a huge file stays cheap
+    let byte_range = position_to_byte(&file, range.start)..position_to_byte(&file, range.end);
     let mut st = SymbolTable::new(typedb);
-    st.build_table(tree, &file);
+    st.build_table_in_ranges(tree, &file, vec![byte_range]);
 
     for scope in st.map.values() {
         for symbol in &scope.vars {
-            if !range_contains(range, symbol.hint_position) {
+            if !range_contains(range, symbol.hint_position)
+                || is_inside_error(tree, symbol.hint_position)
+            {
                 continue;
             }
             match symbol.kind {
@@ -65,3 +83,174 @@ pub fn make_inlay_hints(
 
     hints
 }
+
+fn is_inside_error(tree: &Tree, position: Position) -> bool {
+    let point = position_to_point(position);
+    let mut node = tree.root_node().descendant_for_point_range(point, point);
+    while let Some(cur_node) = node {
+        if cur_node.is_error() {
+            return true;
+        }
+        node = cur_node.parent();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use async_lsp::lsp_types::{InlayHintKind, InlayHintLabel, Position, Range};
+
+    use crate::{
+        filedb::FileDatabase,
+        symbol_table::SymbolTable,
+        test_utils::TEST_TYPEDB,
+        utils::{parse_file, position_to_byte},
+    };
+
+    use super::make_inlay_hints;
+
+    #[test]
+    fn no_hints_inside_parse_errors() {
+        let file = "func foo():
+\tvar a = 10
+
+func bar():
+\t))) var b = 20 (((";
+        assert!(parse_file(file).unwrap().root_node().has_error());
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let hints = make_inlay_hints(range, "/test.gd", &TEST_TYPEDB, &filedb);
+        let labels = hints
+            .iter()
+            .map(|hint| match &hint.label {
+                InlayHintLabel::String(label) => (hint.position, label.as_str()),
+                _ => (hint.position, ""),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![(Position::new(1, 6), ": int")]);
+    }
+
+    #[test]
+    fn empty_files_have_no_hints() {
+        for file in ["", "  \n\t\n", "# only a comment\n# and another\n"] {
+            let filedb = FileDatabase::default();
+            filedb.file_opened("/test.gd", file.to_string()).unwrap();
+            let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+            let hints = make_inlay_hints(range, "/test.gd", &TEST_TYPEDB, &filedb);
+            assert!(hints.is_empty(), "file {file:?}");
+        }
+    }
+
+    #[test]
+    fn tiny_range_of_large_file() {
+        let mut file = (0..2000)
+            .map(|i| format!("func f{i}():\n\tvar a{i} = {i}\n\tvar b{i} = a{i} * 0.5\n"))
+            .collect::<String>();
+        // called from the visible range, but declared far below it
+        file += "func helper() -> Vector2:\n\treturn Vector2.ZERO\n";
+        file += "func inferred_helper():\n\treturn 1.5\n";
+        file = file.replacen(
+            "\tvar b1000 = a1000 * 0.5",
+            "\tvar b1000 = helper()\n\tvar c1000 = inferred_helper()",
+            1,
+        );
+        let range = Range::new(Position::new(3001, 0), Position::new(3003, 100));
+
+        let tree = parse_file(&file).unwrap();
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        let byte_range = position_to_byte(&file, range.start)..position_to_byte(&file, range.end);
+        st.build_table_in_ranges(&tree, &file, vec![byte_range]);
+        // the root scope and the body of the only function in the range
+        assert_eq!(st.map.len(), 2);
+
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file).unwrap();
+        let hints = make_inlay_hints(range, "/test.gd", &TEST_TYPEDB, &filedb);
+        let mut labels = hints
+            .iter()
+            .map(|hint| match &hint.label {
+                InlayHintLabel::String(label) => (hint.position.line, label.as_str()),
+                _ => (hint.position.line, ""),
+            })
+            .collect::<Vec<_>>();
+        labels.sort();
+        assert_eq!(
+            labels,
+            vec![(3001, ": int"), (3002, ": Vector2"), (3003, ": float")]
+        );
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_tiny_range_of_large_file() {
+        const ITERATIONS: u32 = 20;
+        // every function calls the next one, a tiny range needs a single return type
+        let file = (0..2000)
+            .map(|i| {
+                format!(
+                    "func f{i}():\n\tvar a{i} = f{}()\n\treturn {i} * 0.5\n",
+                    i + 1
+                )
+            })
+            .collect::<String>();
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/bench.gd", file).unwrap();
+
+        let measure = |range: Range| {
+            let start = Instant::now();
+            let mut hints = 0;
+            for _ in 0..ITERATIONS {
+                hints = make_inlay_hints(range, "/bench.gd", &TEST_TYPEDB, &filedb).len();
+            }
+            (start.elapsed() / ITERATIONS, hints)
+        };
+        let (full_time, full_hints) =
+            measure(Range::new(Position::new(0, 0), Position::new(u32::MAX, 0)));
+        let (tiny_time, tiny_hints) =
+            measure(Range::new(Position::new(3000, 0), Position::new(3002, 100)));
+        eprintln!(
+            "full range: {full_time:?} for {full_hints} hints, \
+             tiny range: {tiny_time:?} for {tiny_hints} hints"
+        );
+        assert_eq!(tiny_hints, 1);
+    }
+
+    #[test]
+    fn hint_lands_on_identifier_end_for_any_indentation() {
+        for indent in ["\t", "    ", "\t  ", "  \t", "\t\t"] {
+            let file = format!("func foo():\n{indent}var speed = 10\n{indent}var a: int = speed");
+            let filedb = FileDatabase::default();
+            filedb.file_opened("/test.gd", file.clone()).unwrap();
+            let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+            let hints = make_inlay_hints(range, "/test.gd", &TEST_TYPEDB, &filedb);
+            assert_eq!(hints.len(), 1, "indent {indent:?}");
+            let position = hints[0].position;
+            assert_eq!(
+                position,
+                Position::new(1, (indent.len() + "var speed".len()) as u32),
+                "indent {indent:?}"
+            );
+            let line = file.lines().nth(position.line as usize).unwrap();
+            assert!(line[..position.character as usize].ends_with("speed"));
+        }
+    }
+
+    #[test]
+    fn gdscript_function_return_type_is_hinted() {
+        let file = "func foo():
+\tvar count = len(\"abc\")";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let range = Range::new(Position::new(0, 0), Position::new(u32::MAX, 0));
+        let hints = make_inlay_hints(range, "/test.gd", &TEST_TYPEDB, &filedb);
+        let type_hint = hints
+            .iter()
+            .find(|hint| hint.kind == Some(InlayHintKind::TYPE))
+            .unwrap();
+        assert_eq!(type_hint.position, Position::new(1, 10));
+        assert_eq!(type_hint.label, InlayHintLabel::String(": int".to_string()));
+    }
+}
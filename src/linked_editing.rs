@@ -0,0 +1,69 @@
+use async_lsp::lsp_types::*;
+
+use crate::{
+    filedb::FileDatabase, references::collect_references, symbol_table::SymbolTable,
+    typedb::TypeDatabase,
+};
+
+/// Characters the client may type without breaking the link between the ranges
+const IDENTIFIER_PATTERN: &str = "[A-Za-z_][A-Za-z0-9_]*";
+
+/// Returns declaration and usages of the local variable under the cursor, class members
+/// are left to rename because they can be used from other scripts
+pub fn make_linked_editing_ranges(
+    path: &str,
+    position: Position,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+) -> Option<LinkedEditingRanges> {
+    let lock = filedb.files.read();
+    let source_file = lock.get(path)?;
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let mut st = SymbolTable::new(typedb);
+    st.build_table(tree, &file);
+    let symbol = st.symbol_id_at(tree.root_node(), position, &file)?;
+    if symbol.0 == st.root_scope() {
+        return None;
+    }
+    Some(LinkedEditingRanges {
+        ranges: collect_references(&st, tree.root_node(), &file, symbol),
+        word_pattern: Some(IDENTIFIER_PATTERN.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{Position, Range};
+
+    use crate::{filedb::FileDatabase, test_utils::TEST_TYPEDB};
+
+    use super::make_linked_editing_ranges;
+
+    #[test]
+    fn local_variable_usages_are_linked() {
+        let file = "var speed = 1
+
+func foo():
+\tvar speed = 2
+\tprint(speed + speed)";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let linked =
+            make_linked_editing_ranges("/test.gd", Position::new(4, 8), &TEST_TYPEDB, &filedb)
+                .unwrap();
+        assert_eq!(
+            linked.ranges,
+            vec![
+                Range::new(Position::new(3, 5), Position::new(3, 10)),
+                Range::new(Position::new(4, 7), Position::new(4, 12)),
+                Range::new(Position::new(4, 15), Position::new(4, 20)),
+            ]
+        );
+        assert!(
+            make_linked_editing_ranges("/test.gd", Position::new(0, 5), &TEST_TYPEDB, &filedb)
+                .is_none()
+        );
+    }
+}
@@ -1,12 +1,46 @@
+mod add_await;
+mod annotate_type;
+mod cancellation;
+mod classdb;
+mod commands;
+mod completion;
+mod config;
+mod convert_print;
+mod diagnostics;
+mod document_link;
+mod document_symbols;
 mod extract_into_function;
 mod filedb;
+mod formatting;
+mod hover;
+mod indexing;
 mod inlay_hints;
+mod linked_editing;
+mod move_inner_class;
+mod override_virtual;
+mod perf;
+mod prefix_underscore;
+mod references;
+mod rename;
+mod scenedb;
+mod sort_members;
+mod surround_with;
 mod symbol_table;
+#[cfg(test)]
+mod test_utils;
+mod toggle_lambda;
+mod type_definition;
+mod type_hierarchy;
 mod typedb;
 pub mod utils;
 
+use std::collections::HashSet;
 use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
 
+use add_await::add_await_action;
+use annotate_type::annotate_type_action;
 use async_lsp::client_monitor::ClientProcessMonitorLayer;
 use async_lsp::concurrency::ConcurrencyLayer;
 use async_lsp::lsp_types::*;
@@ -15,19 +49,56 @@ use async_lsp::router::Router;
 use async_lsp::server::LifecycleLayer;
 use async_lsp::tracing::TracingLayer;
 use async_lsp::{ClientSocket, LanguageServer, ResponseError};
+use cancellation::CancellationToken;
+use classdb::ClassDatabase;
+use commands::{
+    ApplySnippetWorkspaceEdit, EXTRACT_FUNCTION, TOGGLE_INLAY_HINTS, extract_function,
+    snippet_edit_params, supported_commands, toggle_inlay_hints,
+};
+use completion::{make_completions, resolve_completion_item};
+use config::{ClientFeatures, Config};
+use convert_print::convert_print_actions;
+use diagnostics::{make_diagnostics, parse_failure_diagnostic};
+use document_link::make_document_links;
+use document_symbols::make_document_symbols;
 use filedb::FileDatabase;
+use formatting::{format_on_type, format_range};
 use futures::future::BoxFuture;
+use hover::make_hover;
+use indexing::{
+    IndexingComplete, apply_file_events, index_workspace_in_background, watched_files_registration,
+};
 use inlay_hints::make_inlay_hints;
+use linked_editing::make_linked_editing_ranges;
+use move_inner_class::move_inner_class_action;
+use override_virtual::override_virtual_actions;
+use prefix_underscore::prefix_underscore_actions;
+use rename::rename;
+use scenedb::SceneDatabase;
+use sort_members::{SORT_MEMBERS, sort_members_action};
+use surround_with::surround_with_actions;
+use toggle_lambda::toggle_lambda_action;
 
 use extract_into_function::extract_into_function_action;
 use tower::ServiceBuilder;
-use tracing::Level;
+use tracing_subscriber::util::SubscriberInitExt;
+use type_definition::find_type_definition;
+use type_hierarchy::{prepare_type_hierarchy, subtypes, supertypes};
 use typedb::TypeDatabase;
+use utils::uri_to_path;
 
 struct Backend {
     client: ClientSocket,
-    typedb: TypeDatabase,
+    typedb: Arc<TypeDatabase>,
     filedb: FileDatabase,
+    classdb: ClassDatabase,
+    scenedb: SceneDatabase,
+    workspace_root: Option<PathBuf>,
+    /// Parent of the tokens of all background work, cancelled on shutdown
+    background: CancellationToken,
+    inlay_hints_disabled: HashSet<String>,
+    config: Config,
+    client_features: ClientFeatures,
 }
 
 impl LanguageServer for Backend {
@@ -36,11 +107,35 @@ impl LanguageServer for Backend {
 
     fn initialize(
         &mut self,
-        _: InitializeParams,
+        params: InitializeParams,
     ) -> BoxFuture<'static, Result<InitializeResult, Self::Error>> {
+        self.config = Config::from_initialization_options(params.initialization_options);
+        self.client_features = ClientFeatures::from_capabilities(&params.capabilities);
+
+        #[allow(deprecated)]
+        let root_uri = params
+            .workspace_folders
+            .and_then(|folders| folders.into_iter().next())
+            .map(|folder| folder.uri)
+            .or(params.root_uri);
+        self.workspace_root = root_uri.and_then(|uri| uri.to_file_path().ok());
+        // the bundled type database stays in use unless the project provides its own
+        if let Some(typedb) = TypeDatabase::from_workspace(
+            self.workspace_root.as_deref(),
+            self.config.type_info_path.as_deref(),
+        ) {
+            self.typedb = Arc::new(typedb);
+        }
+
         let mut result = InitializeResult::default();
         let code_action_options = CodeActionOptions {
-            code_action_kinds: Some(vec![CodeActionKind::REFACTOR_EXTRACT]),
+            code_action_kinds: Some(vec![
+                CodeActionKind::REFACTOR_EXTRACT,
+                CodeActionKind::REFACTOR_REWRITE,
+                CodeActionKind::REFACTOR,
+                CodeActionKind::QUICKFIX,
+                CodeActionKind::new(SORT_MEMBERS),
+            ]),
             work_done_progress_options: WorkDoneProgressOptions {
                 work_done_progress: None,
             },
@@ -48,10 +143,38 @@ impl LanguageServer for Backend {
         };
         result.capabilities = ServerCapabilities {
             code_action_provider: Some(CodeActionProviderCapability::Options(code_action_options)),
+            completion_provider: Some(CompletionOptions {
+                resolve_provider: Some(true),
+                trigger_characters: Some(vec![".".to_string(), "$".to_string()]),
+                ..Default::default()
+            }),
             inlay_hint_provider: Some(OneOf::Left(true)),
             text_document_sync: Some(TextDocumentSyncCapability::Kind(
                 TextDocumentSyncKind::INCREMENTAL,
             )),
+            type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            document_link_provider: Some(DocumentLinkOptions {
+                resolve_provider: None,
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+            }),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
+            document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: "\n".to_string(),
+                more_trigger_character: Some(vec![":".to_string()]),
+            }),
+            type_hierarchy_provider: Some(TypeHierarchyServerCapabilities::Simple(true)),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: supported_commands(),
+                work_done_progress_options: WorkDoneProgressOptions {
+                    work_done_progress: None,
+                },
+            }),
             ..Default::default()
         };
         Box::pin(async move { Ok(result) })
@@ -61,34 +184,145 @@ impl LanguageServer for Backend {
         &mut self,
         params: CodeActionParams,
     ) -> BoxFuture<'static, Result<Option<CodeActionResponse>, Self::Error>> {
-        Box::pin(async move {
-            let mut actions = Vec::new();
+        let mut actions = prefix_underscore_actions(&params);
+        if let Some(action) = add_await_action(&params, &self.filedb) {
+            actions.push(action);
+        }
 
-            if let Some(action) = extract_into_function_action(&params) {
-                actions.push(action);
-            }
+        if let Some(action) = extract_into_function_action(&params, &self.config, &self.filedb) {
+            actions.push(action);
+        }
+        if let Some(action) = annotate_type_action(&params, &self.typedb, &self.filedb) {
+            actions.push(action);
+        }
+        if let Some(action) = toggle_lambda_action(&params, &self.filedb) {
+            actions.push(action);
+        }
+        actions.extend(surround_with_actions(&params, &self.filedb));
+        actions.extend(convert_print_actions(&params, &self.filedb));
+        actions.extend(override_virtual_actions(
+            &params,
+            &self.typedb,
+            &self.filedb,
+        ));
+        if let Some(action) = move_inner_class_action(&params, &self.filedb) {
+            actions.push(action);
+        }
+        if let Some(action) = sort_members_action(&params, &self.typedb, &self.filedb) {
+            actions.push(action);
+        }
+
+        Box::pin(async move { Ok(Some(actions)) })
+    }
 
-            Ok(Some(actions))
+    fn completion(
+        &mut self,
+        params: CompletionParams,
+    ) -> BoxFuture<'static, Result<Option<CompletionResponse>, Self::Error>> {
+        let (typedb, filedb, scenedb) = (
+            self.typedb.clone(),
+            self.filedb.clone(),
+            self.scenedb.clone(),
+        );
+        let cancellation = self.background.child_token();
+        let guard = cancellation.drop_guard();
+        Box::pin(async move {
+            let _guard = guard;
+            let items = tokio::task::spawn_blocking(move || {
+                make_completions(
+                    &uri_to_path(&params.text_document_position.text_document.uri),
+                    params.text_document_position.position,
+                    &typedb,
+                    &filedb,
+                    &scenedb,
+                    &cancellation,
+                )
+            })
+            .await
+            .unwrap_or_default();
+            Ok(Some(CompletionResponse::Array(items)))
         })
     }
 
+    fn completion_item_resolve(
+        &mut self,
+        params: CompletionItem,
+    ) -> BoxFuture<'static, Result<CompletionItem, Self::Error>> {
+        let item = resolve_completion_item(
+            params,
+            &self.typedb,
+            self.client_features.markdown_documentation,
+        );
+        Box::pin(async move { Ok(item) })
+    }
+
     fn shutdown(&mut self, _params: ()) -> BoxFuture<'static, Result<(), Self::Error>> {
+        // running analysis stops early so the process can exit without waiting for it
+        self.background.cancel();
+        self.filedb.files.write().clear();
+        self.classdb.classes.write().clear();
+        self.scenedb.scenes.write().clear();
         Box::pin(async move { Ok(()) })
     }
 
-    // fn initialized(&mut self, _: InitializedParams) {
-    //     self.client
-    //         .log_message(MessageType::INFO, "server initialized!")
-    //         .await;
-    // }
+    fn initialized(&mut self, _: InitializedParams) -> Self::NotifyResult {
+        // notifications can't be sent before the client receives `initialize` result,
+        // so the workspace is scanned only after that
+        if let Some(root) = self.workspace_root.clone() {
+            let client = self.client.clone();
+            tokio::spawn(index_workspace_in_background(
+                root,
+                self.classdb.clone(),
+                self.scenedb.clone(),
+                move |params| {
+                    let _ = client.notify::<IndexingComplete>(params);
+                },
+            ));
+        }
+        // clients without dynamic registration for watched files would reject the request
+        if self.client_features.watched_files_registration {
+            let client = self.client.clone();
+            tokio::spawn(async move {
+                let params = RegistrationParams {
+                    registrations: vec![watched_files_registration()],
+                };
+                if let Err(error) = client.request::<request::RegisterCapability>(params).await {
+                    tracing::warn!("Watching workspace files isn't supported: {error}");
+                }
+            });
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn did_change_watched_files(
+        &mut self,
+        params: DidChangeWatchedFilesParams,
+    ) -> Self::NotifyResult {
+        if let Some(root) = &self.workspace_root {
+            apply_file_events(
+                &params.changes,
+                root,
+                &self.filedb,
+                &self.classdb,
+                &self.scenedb,
+            );
+        }
+        ControlFlow::Continue(())
+    }
 
     fn inlay_hint(
         &mut self,
         params: InlayHintParams,
     ) -> BoxFuture<'static, Result<Option<Vec<InlayHint>>, Self::Error>> {
+        if self
+            .inlay_hints_disabled
+            .contains(&uri_to_path(&params.text_document.uri))
+        {
+            return Box::pin(async move { Ok(None) });
+        }
         let vec = make_inlay_hints(
             params.range,
-            params.text_document.uri.path(),
+            &uri_to_path(&params.text_document.uri),
             &self.typedb,
             &self.filedb,
         );
@@ -101,13 +335,226 @@ impl LanguageServer for Backend {
         })
     }
 
+    fn execute_command(
+        &mut self,
+        params: ExecuteCommandParams,
+    ) -> BoxFuture<'static, Result<Option<serde_json::Value>, Self::Error>> {
+        let client = self.client.clone();
+        match params.command.as_str() {
+            TOGGLE_INLAY_HINTS => {
+                let result = toggle_inlay_hints(&mut self.inlay_hints_disabled, &params.arguments)
+                    .map(serde_json::Value::Bool);
+                Box::pin(async move {
+                    if result.is_some() {
+                        let _ = client.request::<request::InlayHintRefreshRequest>(()).await;
+                    }
+                    Ok(result)
+                })
+            }
+            EXTRACT_FUNCTION => {
+                let snippets = self.client_features.snippet_support;
+                let edit =
+                    extract_function(&self.config, &self.filedb, snippets, &params.arguments);
+                Box::pin(async move {
+                    let Some(edit) = edit else {
+                        return Ok(None);
+                    };
+                    let params = ApplyWorkspaceEditParams {
+                        label: Some("Extract into function".to_string()),
+                        edit,
+                    };
+                    // the result tells whether the client applied the edit
+                    let response = if snippets {
+                        client
+                            .request::<ApplySnippetWorkspaceEdit>(snippet_edit_params(params))
+                            .await
+                    } else {
+                        client.request::<request::ApplyWorkspaceEdit>(params).await
+                    };
+                    let applied = response.is_ok_and(|response| response.applied);
+                    Ok(Some(serde_json::Value::Bool(applied)))
+                })
+            }
+            _ => Box::pin(async move { Ok(None) }),
+        }
+    }
+
+    fn hover(
+        &mut self,
+        params: HoverParams,
+    ) -> BoxFuture<'static, Result<Option<Hover>, Self::Error>> {
+        let hover = make_hover(
+            &uri_to_path(&params.text_document_position_params.text_document.uri),
+            params.text_document_position_params.position,
+            &self.typedb,
+            &self.filedb,
+            self.client_features.markdown_hover,
+        );
+        Box::pin(async move { Ok(hover) })
+    }
+
+    fn document_symbol(
+        &mut self,
+        params: DocumentSymbolParams,
+    ) -> BoxFuture<'static, Result<Option<DocumentSymbolResponse>, Self::Error>> {
+        let symbols = make_document_symbols(&uri_to_path(&params.text_document.uri), &self.filedb);
+        Box::pin(async move { Ok(Some(DocumentSymbolResponse::Nested(symbols))) })
+    }
+
+    fn rename(
+        &mut self,
+        params: RenameParams,
+    ) -> BoxFuture<'static, Result<Option<WorkspaceEdit>, Self::Error>> {
+        // renaming a class reads the closed scripts of the workspace from disk
+        let (typedb, filedb, classdb, workspace_root) = (
+            self.typedb.clone(),
+            self.filedb.clone(),
+            self.classdb.clone(),
+            self.workspace_root.clone(),
+        );
+        Box::pin(async move {
+            let edit = tokio::task::spawn_blocking(move || {
+                rename(
+                    &uri_to_path(&params.text_document_position.text_document.uri),
+                    params.text_document_position.position,
+                    &params.new_name,
+                    &typedb,
+                    &filedb,
+                    &classdb,
+                    workspace_root.as_deref(),
+                )
+            })
+            .await
+            .unwrap_or_default();
+            Ok(edit)
+        })
+    }
+
+    fn document_link(
+        &mut self,
+        params: DocumentLinkParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<DocumentLink>>, Self::Error>> {
+        let links = make_document_links(
+            &uri_to_path(&params.text_document.uri),
+            &self.filedb,
+            self.workspace_root.as_deref(),
+        );
+        Box::pin(async move { Ok(Some(links)) })
+    }
+
+    fn linked_editing_range(
+        &mut self,
+        params: LinkedEditingRangeParams,
+    ) -> BoxFuture<'static, Result<Option<LinkedEditingRanges>, Self::Error>> {
+        let ranges = make_linked_editing_ranges(
+            &uri_to_path(&params.text_document_position_params.text_document.uri),
+            params.text_document_position_params.position,
+            &self.typedb,
+            &self.filedb,
+        );
+        Box::pin(async move { Ok(ranges) })
+    }
+
+    fn type_definition(
+        &mut self,
+        params: GotoTypeDefinitionParams,
+    ) -> BoxFuture<'static, Result<Option<GotoTypeDefinitionResponse>, Self::Error>> {
+        let location = find_type_definition(
+            &uri_to_path(&params.text_document_position_params.text_document.uri),
+            params.text_document_position_params.position,
+            &self.typedb,
+            &self.filedb,
+            &self.classdb,
+        );
+        Box::pin(async move { Ok(location.map(GotoTypeDefinitionResponse::Scalar)) })
+    }
+
+    fn range_formatting(
+        &mut self,
+        params: DocumentRangeFormattingParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<TextEdit>>, Self::Error>> {
+        let filedb = self.filedb.clone();
+        let cancellation = self.background.child_token();
+        let guard = cancellation.drop_guard();
+        Box::pin(async move {
+            let _guard = guard;
+            let edits = tokio::task::spawn_blocking(move || {
+                format_range(
+                    &uri_to_path(&params.text_document.uri),
+                    params.range,
+                    &params.options,
+                    &filedb,
+                    &cancellation,
+                )
+            })
+            .await
+            .unwrap_or_default();
+            Ok(Some(edits))
+        })
+    }
+
+    fn on_type_formatting(
+        &mut self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<TextEdit>>, Self::Error>> {
+        let edits = format_on_type(
+            &uri_to_path(&params.text_document_position.text_document.uri),
+            params.text_document_position.position,
+            &params.ch,
+            &params.options,
+            &self.filedb,
+        );
+        Box::pin(async move { Ok(Some(edits)) })
+    }
+
+    fn prepare_type_hierarchy(
+        &mut self,
+        params: TypeHierarchyPrepareParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<TypeHierarchyItem>>, Self::Error>> {
+        let items = prepare_type_hierarchy(
+            &params.text_document_position_params.text_document.uri,
+            params.text_document_position_params.position,
+            &self.typedb,
+            &self.filedb,
+            &self.classdb,
+        );
+        Box::pin(async move { Ok(items) })
+    }
+
+    fn supertypes(
+        &mut self,
+        params: TypeHierarchySupertypesParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<TypeHierarchyItem>>, Self::Error>> {
+        let items = supertypes(&params.item, &self.typedb, &self.classdb);
+        Box::pin(async move { Ok(Some(items)) })
+    }
+
+    fn subtypes(
+        &mut self,
+        params: TypeHierarchySubtypesParams,
+    ) -> BoxFuture<'static, Result<Option<Vec<TypeHierarchyItem>>, Self::Error>> {
+        let items = subtypes(&params.item, &self.typedb, &self.classdb);
+        Box::pin(async move { Ok(Some(items)) })
+    }
+
     fn did_open(
         &mut self,
         params: DidOpenTextDocumentParams,
     ) -> ControlFlow<Result<(), async_lsp::Error>> {
-        let file_path = params.text_document.uri.path();
-        self.filedb
-            .file_opened(file_path, params.text_document.text);
+        let file_path = &uri_to_path(&params.text_document.uri);
+        match self
+            .filedb
+            .file_opened(file_path, params.text_document.text)
+        {
+            Ok(()) => {
+                self.classdb.file_updated(file_path, &self.filedb);
+                self.publish_diagnostics(params.text_document.uri);
+            }
+            Err(error) => self.send_diagnostics(
+                params.text_document.uri,
+                vec![parse_failure_diagnostic(&error)],
+            ),
+        }
         ControlFlow::Continue(())
     }
 
@@ -122,8 +569,10 @@ impl LanguageServer for Backend {
         &mut self,
         params: DidChangeTextDocumentParams,
     ) -> ControlFlow<Result<(), async_lsp::Error>> {
-        let file_path = params.text_document.uri.path();
+        let file_path = &uri_to_path(&params.text_document.uri);
         self.filedb.file_changed(file_path, params.content_changes);
+        self.classdb.file_updated(file_path, &self.filedb);
+        self.publish_diagnostics(params.text_document.uri);
         ControlFlow::Continue(())
     }
 
@@ -136,15 +585,41 @@ impl LanguageServer for Backend {
 }
 
 impl Backend {
-    fn new_router(client: ClientSocket) -> Router<Self> {
+    fn new(client: ClientSocket) -> Self {
         const TYPE_INFO: &str = include_str!("../assets/type_info.json");
-        let typedb = TypeDatabase::from_str(TYPE_INFO).unwrap();
+        let typedb = Arc::new(TypeDatabase::from_str(TYPE_INFO).unwrap());
 
-        Router::from_language_server(Self {
+        Self {
             client,
             typedb,
             filedb: FileDatabase::default(),
-        })
+            classdb: ClassDatabase::default(),
+            scenedb: SceneDatabase::default(),
+            workspace_root: None,
+            background: CancellationToken::default(),
+            inlay_hints_disabled: HashSet::new(),
+            config: Config::default(),
+            client_features: ClientFeatures::default(),
+        }
+    }
+
+    fn new_router(client: ClientSocket) -> Router<Self> {
+        Router::from_language_server(Self::new(client))
+    }
+
+    fn publish_diagnostics(&self, uri: Url) {
+        let diagnostics = make_diagnostics(&uri_to_path(&uri), &self.typedb, &self.filedb);
+        self.send_diagnostics(uri, diagnostics);
+    }
+
+    fn send_diagnostics(&self, uri: Url, diagnostics: Vec<Diagnostic>) {
+        let _ = self
+            .client
+            .notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams {
+                uri,
+                diagnostics,
+                version: None,
+            });
     }
 }
 
@@ -160,11 +635,7 @@ async fn main() {
             .service(Backend::new_router(client))
     });
 
-    tracing_subscriber::fmt()
-        .with_max_level(Level::INFO)
-        .with_ansi(false)
-        .with_writer(std::io::stderr)
-        .init();
+    perf::log_subscriber(perf::perf_logging_enabled(), std::io::stderr).init();
 
     // Prefer truly asynchronous piped stdin/stdout without blocking tasks.
     #[cfg(unix)]
@@ -181,3 +652,69 @@ async fn main() {
 
     server.run_buffered(stdin, stdout).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::{ClientSocket, LanguageServer, lsp_types::*};
+    use serde_json::{Value, json};
+
+    use crate::test_utils::{TEST_PATH, test_uri};
+
+    use super::{Backend, TOGGLE_INLAY_HINTS};
+
+    #[test]
+    fn shutdown_cancels_background_work_and_clears_state() {
+        let mut backend = Backend::new(ClientSocket::new_closed());
+        backend
+            .filedb
+            .file_opened("/test.gd", "class_name Player".to_string())
+            .unwrap();
+        backend.classdb.file_updated("/test.gd", &backend.filedb);
+        let task = backend.background.child_token();
+
+        drop(backend.shutdown(()));
+        assert!(task.is_cancelled());
+        assert!(backend.filedb.files.read().is_empty());
+        assert!(backend.classdb.classes.read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn toggled_off_file_gets_no_inlay_hints() {
+        let mut backend = Backend::new(ClientSocket::new_closed());
+        backend
+            .filedb
+            .file_opened(TEST_PATH, "func foo():\n\tvar speed = 10".to_string())
+            .unwrap();
+        let hint_params = InlayHintParams {
+            text_document: TextDocumentIdentifier { uri: test_uri() },
+            range: Range::new(Position::new(0, 0), Position::new(u32::MAX, 0)),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let toggle_params = ExecuteCommandParams {
+            command: TOGGLE_INLAY_HINTS.to_string(),
+            arguments: vec![json!(test_uri())],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        assert!(
+            backend
+                .inlay_hint(hint_params.clone())
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        let enabled = backend.execute_command(toggle_params.clone()).await;
+        assert_eq!(enabled.unwrap(), Some(Value::Bool(false)));
+        assert!(
+            backend
+                .inlay_hint(hint_params.clone())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let enabled = backend.execute_command(toggle_params).await;
+        assert_eq!(enabled.unwrap(), Some(Value::Bool(true)));
+        assert!(backend.inlay_hint(hint_params).await.unwrap().is_some());
+    }
+}
@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use async_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{
+    filedb::FileDatabase,
+    utils::{node_content, point_to_position, position_to_point, uri_to_path},
+};
+
+/// Offers to move the top level inner class under the cursor into a `snake_case.gd` file
+/// next to the script. The new script gets `class_name`, so the definition is removed
+/// instead of being replaced with a preload that would shadow the global class
+pub fn move_inner_class_action(
+    params: &CodeActionParams,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+
+    let point = position_to_point(params.range.start);
+    let mut class = source_file
+        .tree
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    while class.kind() != "class_definition" {
+        class = class.parent()?;
+    }
+    if class.parent()?.kind() != "source" {
+        return None;
+    }
+    let name = node_content(&class.child_by_field_name("name")?, &file);
+    let body = class.child_by_field_name("body")?;
+
+    let path = uri_to_path(uri);
+    let new_path = Path::new(&path)
+        .parent()?
+        .join(format!("{}.gd", snake_case(name)));
+    let new_uri = Url::from_file_path(new_path).ok()?;
+
+    let mut cursor = class.walk();
+    let extends = class
+        .children(&mut cursor)
+        .find(|child| child.kind() == "extends_statement")
+        .map_or(String::new(), |extends| {
+            format!("{}\n", node_content(&extends, &file))
+        });
+    let new_content = format!(
+        "class_name {name}\n{extends}\n{}\n",
+        dedented_body(class, body, &file)
+    );
+
+    let removed_range = match (class.next_named_sibling(), class.prev_named_sibling()) {
+        (Some(next), _) => Range::new(
+            Position::new(class.start_position().row as u32, 0),
+            Position::new(next.start_position().row as u32, 0),
+        ),
+        (None, Some(previous)) => Range::new(
+            point_to_position(previous.end_position()),
+            point_to_position(class.end_position()),
+        ),
+        (None, None) => Range::new(
+            Position::new(class.start_position().row as u32, 0),
+            point_to_position(class.end_position()),
+        ),
+    };
+
+    let text_edit = |uri: &Url, range, new_text| {
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: None,
+            },
+            edits: vec![OneOf::Left(TextEdit::new(range, new_text))],
+        })
+    };
+    let operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: new_uri.clone(),
+            options: Some(CreateFileOptions {
+                overwrite: Some(false),
+                ignore_if_exists: Some(false),
+            }),
+            annotation_id: None,
+        })),
+        text_edit(&new_uri, Range::default(), new_content),
+        text_edit(uri, removed_range, String::new()),
+    ];
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Move `{name}` to a new file"),
+        kind: Some(CodeActionKind::REFACTOR),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Returns class body with the indent of its first line removed from every line,
+/// a body written on the line of the header is returned as is
+fn dedented_body(class: Node, body: Node, file: &str) -> String {
+    if body.start_position().row == class.start_position().row {
+        return node_content(&body, file).to_string();
+    }
+    let line_start = file[..body.start_byte()]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let content = &file[line_start..class.end_byte()];
+    let indent = &content[..content.len() - content.trim_start().len()];
+    content
+        .lines()
+        .map(|line| line.strip_prefix(indent).unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `InnerEnemy` -> `inner_enemy`, file naming used by Godot for new scripts
+fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous_is_lower = false;
+    for ch in name.chars() {
+        if ch.is_uppercase() && previous_is_lower {
+            result.push('_');
+        }
+        previous_is_lower = ch.is_lowercase() || ch.is_ascii_digit();
+        result.extend(ch.to_lowercase());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::{
+        filedb::FileDatabase,
+        test_utils::{apply_edits, code_action_params},
+    };
+
+    use super::move_inner_class_action;
+
+    #[test]
+    fn inner_class_is_moved_to_new_file() {
+        let file = "extends Node
+
+class InnerEnemy extends Node2D:
+\tvar health = 10
+
+\tfunc hit():
+\t\thealth -= 1
+
+func foo():
+\tpass";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/level.gd", file.to_string())
+            .unwrap();
+        let uri = Url::from_file_path("/project/level.gd").unwrap();
+        let position = Position::new(2, 8);
+        let mut params = code_action_params(Range::new(position, position));
+        params.text_document.uri = uri.clone();
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            move_inner_class_action(&params, &filedb)
+        else {
+            panic!("expected code action");
+        };
+        let Some(DocumentChanges::Operations(operations)) = action.edit.unwrap().document_changes
+        else {
+            panic!("expected resource operations");
+        };
+        let new_uri = Url::from_file_path("/project/inner_enemy.gd").unwrap();
+        let [
+            DocumentChangeOperation::Op(ResourceOp::Create(create)),
+            DocumentChangeOperation::Edit(new_file),
+            DocumentChangeOperation::Edit(old_file),
+        ] = operations.as_slice()
+        else {
+            panic!("expected file creation followed by two edits");
+        };
+        assert_eq!(create.uri, new_uri);
+
+        assert_eq!(new_file.text_document.uri, new_uri);
+        let OneOf::Left(edit) = &new_file.edits[0] else {
+            panic!("expected plain text edit");
+        };
+        assert_eq!(
+            edit.new_text,
+            "class_name InnerEnemy
+extends Node2D
+
+var health = 10
+
+func hit():
+\thealth -= 1
+"
+        );
+
+        assert_eq!(old_file.text_document.uri, uri);
+        let OneOf::Left(edit) = &old_file.edits[0] else {
+            panic!("expected plain text edit");
+        };
+        assert_eq!(
+            apply_edits(file, vec![edit.clone()]),
+            "extends Node
+
+func foo():
+\tpass"
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    extract_into_function::function_separator_lines,
+    filedb::FileDatabase,
+    symbol_table::extends_type,
+    typedb::{GLOBAL_SCOPES, SymbolType, TypeDatabase, VariantType},
+    utils::{node_content, point_to_position, uri_to_path},
+};
+
+/// Engine callbacks scripts override most often, in the order they're offered
+const COMMON_VIRTUALS: [&str; 7] = [
+    "_ready",
+    "_process",
+    "_physics_process",
+    "_input",
+    "_unhandled_input",
+    "_enter_tree",
+    "_exit_tree",
+];
+
+/// Offers to append a stub of each common virtual method of the parent class
+/// that the script doesn't override yet, parameters are typed as declared by the engine
+pub fn override_virtual_actions(
+    params: &CodeActionParams,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(&uri_to_path(uri)) else {
+        return Vec::new();
+    };
+    let file = source_file.content.to_string();
+    let root = source_file.tree.root_node();
+
+    let mut cursor = root.walk();
+    let members = root.named_children(&mut cursor).collect::<Vec<_>>();
+    let parent = members
+        .iter()
+        .find_map(|member| extends_type(*member, &file))
+        .unwrap_or(SymbolType::Object("RefCounted".to_string()));
+    let defined_functions = members
+        .iter()
+        .filter(|member| member.kind() == "function_definition")
+        .filter_map(|function| function.child_by_field_name("name"))
+        .map(|name_node| node_content(&name_node, &file))
+        .collect::<Vec<_>>();
+    // stubs go after the last member separated like the other functions
+    let (position, separator) = match members.last() {
+        Some(last) => (
+            point_to_position(last.end_position()),
+            "\n".repeat(function_separator_lines(root, &file) + 1),
+        ),
+        None => (Position::new(0, 0), String::new()),
+    };
+
+    COMMON_VIRTUALS
+        .iter()
+        .filter(|name| !defined_functions.contains(name))
+        .filter_map(|name| {
+            let (owner, method) = typedb.get_callable_with_owner(&parent, name)?;
+            if GLOBAL_SCOPES.contains(&owner.to_string().as_str()) {
+                return None;
+            }
+            let parameters = method
+                .parameters
+                .iter()
+                .map(|parameter| format!("{}: {}", parameter.name, parameter.ttype.to_string()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let return_type = match &method.return_type {
+                SymbolType::Variant(VariantType::Nil) => String::new(),
+                return_type => format!(" -> {}", return_type.to_string()),
+            };
+            let stub = format!("{separator}func {name}({parameters}){return_type}:\n\tpass");
+            let edit = TextEdit::new(Range::new(position, position), stub);
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Override `{name}`"),
+                kind: Some(CodeActionKind::REFACTOR),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{TEST_TYPEDB, apply_action, code_action_params, test_filedb};
+
+    use super::override_virtual_actions;
+
+    #[test]
+    fn process_stub_is_appended() {
+        let file = "extends Node
+
+func _ready():
+\tpass
+";
+        let filedb = test_filedb(file);
+        let params = code_action_params(Range::default());
+        let actions = override_virtual_actions(&params, &TEST_TYPEDB, &filedb);
+        let titles = actions
+            .iter()
+            .filter_map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => Some(action.title.as_str()),
+                CodeActionOrCommand::Command(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(titles[0], "Override `_process`");
+        assert!(!titles.contains(&"Override `_ready`"));
+
+        assert_eq!(
+            apply_action(file, actions.first()).unwrap(),
+            "extends Node
+
+func _ready():
+\tpass
+
+
+func _process(delta: float):
+\tpass
+"
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt::{MakeWriter, format::FmtSpan},
+    layer::SubscriberExt,
+};
+
+/// Target of the spans timing analysis, e.g. `debug_span!(target: PERF_TARGET, "build_table")`
+pub const PERF_TARGET: &str = "godot_sidekick_lsp::perf";
+
+/// Setting this environment variable to any value enables timing logs
+pub const PERF_LOG_VARIABLE: &str = "GODOT_SIDEKICK_PERF_LOG";
+
+pub fn perf_logging_enabled() -> bool {
+    std::env::var_os(PERF_LOG_VARIABLE).is_some()
+}
+
+/// Logs at `INFO` level, with `perf_logging` the durations of the analysis spans
+/// are also reported at `DEBUG` level when the spans close
+pub fn log_subscriber<W>(perf_logging: bool, writer: W) -> impl Subscriber + Send + Sync + 'static
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let (perf_level, span_events) = if perf_logging {
+        (Level::DEBUG, FmtSpan::CLOSE)
+    } else {
+        (Level::INFO, FmtSpan::NONE)
+    };
+    let filter = Targets::new()
+        .with_default(Level::INFO)
+        .with_target(PERF_TARGET, perf_level);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+                .with_span_events(span_events),
+        )
+        .with(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use crate::{symbol_table::SymbolTable, test_utils::TEST_TYPEDB, utils::parse_file};
+
+    use super::log_subscriber;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn analysis_logs(perf_logging: bool) -> String {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = log_subscriber(perf_logging, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let file = "func foo():\n\tvar a = 1";
+            let tree = parse_file(file).unwrap();
+            let mut st = SymbolTable::new(&TEST_TYPEDB);
+            st.build_table(&tree, file);
+        });
+        String::from_utf8(buffer.0.lock().clone()).unwrap()
+    }
+
+    #[test]
+    fn spans_are_timed_only_when_enabled() {
+        let logs = analysis_logs(true);
+        assert!(logs.contains("parse"));
+        assert!(logs.contains("build_table"));
+        assert!(logs.contains("time.busy"));
+
+        assert!(analysis_logs(false).is_empty());
+    }
+}
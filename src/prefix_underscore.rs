@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+
+use crate::diagnostics::{DIAGNOSTIC_SOURCE, UNUSED_VARIABLE};
+
+/// Offers to prefix unused local variables with an underscore, only the declaration changes
+/// because the variable has no usages
+pub fn prefix_underscore_actions(params: &CodeActionParams) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    params
+        .context
+        .diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            diagnostic.source.as_deref() == Some(DIAGNOSTIC_SOURCE)
+                && diagnostic.code == Some(NumberOrString::String(UNUSED_VARIABLE.to_string()))
+        })
+        .map(|diagnostic| {
+            let start = diagnostic.range.start;
+            let edit = TextEdit::new(Range::new(start, start), "_".to_string());
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: "Prefix with underscore".to_string(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(true),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::{
+        diagnostics::make_diagnostics,
+        test_utils::{TEST_PATH, TEST_TYPEDB, apply_action, code_action_params, test_filedb},
+    };
+
+    use super::prefix_underscore_actions;
+
+    #[test]
+    fn unused_declaration_is_prefixed() {
+        let file = "func foo():
+\tvar speed = 1
+\tvar unused = speed";
+        let filedb = test_filedb(file);
+        let mut params = code_action_params(Range::new(Position::new(2, 5), Position::new(2, 5)));
+        params.context.diagnostics = make_diagnostics(TEST_PATH, &TEST_TYPEDB, &filedb);
+        let actions = prefix_underscore_actions(&params);
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected code action");
+        };
+        assert_eq!(action.kind, Some(CodeActionKind::QUICKFIX));
+        assert_eq!(
+            apply_action(file, actions.first()).unwrap(),
+            "func foo():
+\tvar speed = 1
+\tvar _unused = speed"
+        );
+    }
+
+    #[test]
+    fn prefix_lands_after_multibyte_characters() {
+        let file = "func foo():
+\tprint(\"é\"); var unused = 1";
+        let filedb = test_filedb(file);
+        let mut params = code_action_params(Range::new(Position::new(1, 17), Position::new(1, 17)));
+        params.context.diagnostics = make_diagnostics(TEST_PATH, &TEST_TYPEDB, &filedb);
+        let actions = prefix_underscore_actions(&params);
+        assert_eq!(
+            apply_action(file, actions.first()).unwrap(),
+            "func foo():
+\tprint(\"é\"); var _unused = 1"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use async_lsp::lsp_types::Range;
+use tree_sitter::Node;
+
+use crate::{
+    symbol_table::SymbolTable,
+    utils::{node_content, node_to_range, walk_tree},
+};
+
+/// Collects ranges of the declaration and all usages of the symbol in the file
+pub fn collect_references(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    symbol: (usize, usize),
+) -> Vec<Range> {
+    let Some(name) = st
+        .map
+        .get(&symbol.0)
+        .and_then(|scope| scope.vars.get(symbol.1))
+        .map(|var| var.name.as_str())
+    else {
+        return Vec::new();
+    };
+
+    let mut ranges = Vec::new();
+    walk_tree(root, &mut |node| {
+        if !matches!(node.kind(), "identifier" | "name") || node_content(&node, file) != name {
+            return;
+        }
+        if st.resolve_symbol(node, file) == Some(symbol) {
+            ranges.push(node_to_range(&node));
+        }
+    });
+    ranges
+}
+
+/// Collects ranges of the `class_name` declaration and usages of the script class in the file,
+/// e.g. `extends Player`, `var p: Player` and `Player.new()`. Variables of the file
+/// named like the class shadow it, so their usages are skipped
+pub fn collect_class_references(
+    st: &SymbolTable,
+    root: Node,
+    file: &str,
+    class_name: &str,
+) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    walk_tree(root, &mut |node| {
+        if node.child_count() != 0
+            || !matches!(node.kind(), "identifier" | "name" | "type")
+            || node_content(&node, file) != class_name
+            || st.resolve_symbol(node, file).is_some()
+        {
+            return;
+        }
+        // `something.Player` is a member access, not the class
+        let is_member = node
+            .parent()
+            .is_some_and(|parent| parent.kind() == "attribute" && parent.child(0) != Some(node));
+        if !is_member {
+            ranges.push(node_to_range(&node));
+        }
+    });
+    ranges
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, path::Path};
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    classdb::{ClassDatabase, workspace_scripts},
+    filedb::FileDatabase,
+    references::{collect_class_references, collect_references},
+    symbol_table::SymbolTable,
+    typedb::TypeDatabase,
+    utils::{identifier_at, node_content, parse_file},
+};
+
+/// Renames a local variable, parameter or class member together with all of its usages,
+/// renaming a `class_name` updates its usages in every script of the workspace
+pub fn rename(
+    path: &str,
+    position: Position,
+    new_name: &str,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    classdb: &ClassDatabase,
+    workspace_root: Option<&Path>,
+) -> Option<WorkspaceEdit> {
+    let lock = filedb.files.read();
+    let source_file = lock.get(path)?;
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let mut st = SymbolTable::new(typedb);
+    st.build_table(tree, &file);
+    let Some(symbol) = st.symbol_id_at(tree.root_node(), position, &file) else {
+        let node = identifier_at(tree.root_node(), position)?;
+        let class_name = node_content(&node, &file);
+        if !classdb.classes.read().contains_key(class_name) {
+            return None;
+        }
+        drop(lock);
+        return rename_class(class_name, new_name, typedb, filedb, workspace_root);
+    };
+
+    let edits = collect_references(&st, tree.root_node(), &file, symbol)
+        .into_iter()
+        .map(|range| TextEdit::new(range, new_name.to_string()))
+        .collect::<Vec<_>>();
+    let uri = Url::from_file_path(path).ok()?;
+    Some(WorkspaceEdit {
+        changes: Some(HashMap::from([(uri, edits)])),
+        ..Default::default()
+    })
+}
+
+/// Collects edits for all usages of the script class in opened files and in the scripts on disk,
+/// all of them are returned in one edit so the client applies either everything or nothing.
+/// Opened files are taken from their buffers, scripts on disk are parsed only if they
+/// mention the class
+fn rename_class(
+    class_name: &str,
+    new_name: &str,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    workspace_root: Option<&Path>,
+) -> Option<WorkspaceEdit> {
+    let mut changes = HashMap::new();
+    let mut add_changes = |path: &str, file: &str, tree: &tree_sitter::Tree| {
+        let mut st = SymbolTable::new(typedb);
+        st.build_table(tree, file);
+        let edits = collect_class_references(&st, tree.root_node(), file, class_name)
+            .into_iter()
+            .map(|range| TextEdit::new(range, new_name.to_string()))
+            .collect::<Vec<_>>();
+        if edits.is_empty() {
+            return;
+        }
+        if let Ok(uri) = Url::from_file_path(path) {
+            changes.insert(uri, edits);
+        }
+    };
+
+    let lock = filedb.files.read();
+    for (path, source_file) in lock.iter() {
+        let content = source_file.content.to_string();
+        if content.contains(class_name) {
+            add_changes(path, &content, &source_file.tree);
+        }
+    }
+    let closed_scripts = workspace_root
+        .map(workspace_scripts)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|script| !lock.contains_key(script.to_string_lossy().as_ref()));
+    for script in closed_scripts {
+        let Ok(content) = std::fs::read_to_string(&script) else {
+            continue;
+        };
+        if !content.contains(class_name) {
+            continue;
+        }
+        let Some(tree) = parse_file(&content) else {
+            continue;
+        };
+        add_changes(&script.to_string_lossy(), &content, &tree);
+    }
+    Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{Position, Url};
+
+    use crate::{
+        classdb::ClassDatabase, filedb::FileDatabase, test_utils::TEST_TYPEDB, utils::parse_file,
+    };
+
+    use super::rename;
+
+    #[test]
+    fn rename_class_member_skips_shadowing_local() {
+        let file = "var health = 10
+
+func damage():
+\thealth -= 1
+\tself.health = health
+
+func heal():
+\tvar health = 5
+\tprint(health)";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let edit = rename(
+            "/test.gd",
+            Position::new(0, 5),
+            "hp",
+            &TEST_TYPEDB,
+            &filedb,
+            &ClassDatabase::default(),
+            None,
+        )
+        .unwrap();
+        let edits = edit
+            .changes
+            .unwrap()
+            .remove(&Url::from_file_path("/test.gd").unwrap())
+            .unwrap();
+        let mut starts = edits
+            .iter()
+            .map(|edit| edit.range.start)
+            .collect::<Vec<_>>();
+        starts.sort_by_key(|position| (position.line, position.character));
+        assert_eq!(
+            starts,
+            vec![
+                Position::new(0, 4),
+                Position::new(3, 1),
+                Position::new(4, 6),
+                Position::new(4, 15),
+            ]
+        );
+        assert!(edits.iter().all(|edit| edit.new_text == "hp"));
+    }
+
+    #[test]
+    fn rename_local_variable() {
+        let file = "func foo():
+\tvar a = 10
+\tprint(a)";
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/test.gd", file.to_string()).unwrap();
+        let edit = rename(
+            "/test.gd",
+            Position::new(2, 7),
+            "b",
+            &TEST_TYPEDB,
+            &filedb,
+            &ClassDatabase::default(),
+            None,
+        )
+        .unwrap();
+        let edits = edit
+            .changes
+            .unwrap()
+            .remove(&Url::from_file_path("/test.gd").unwrap())
+            .unwrap();
+        assert_eq!(edits.len(), 2);
+    }
+
+    #[test]
+    fn rename_class_name_across_files() {
+        let player = "class_name Player
+extends CharacterBody3D";
+        let enemy = "extends Node
+var target: Player
+func spawn():
+\ttarget = Player.new()";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/player.gd", player.to_string())
+            .unwrap();
+        filedb
+            .file_opened("/project/enemy.gd", enemy.to_string())
+            .unwrap();
+        let classdb = ClassDatabase::default();
+        classdb.update_class("/project/player.gd", &parse_file(player).unwrap(), player);
+
+        let edit = rename(
+            "/project/enemy.gd",
+            Position::new(1, 14),
+            "Hero",
+            &TEST_TYPEDB,
+            &filedb,
+            &classdb,
+            None,
+        )
+        .unwrap();
+        let changes = edit.changes.unwrap();
+        assert_eq!(changes.len(), 2);
+        let player_edits = &changes[&Url::from_file_path("/project/player.gd").unwrap()];
+        assert_eq!(player_edits.len(), 1);
+        assert_eq!(player_edits[0].range.start, Position::new(0, 11));
+        let enemy_edits = &changes[&Url::from_file_path("/project/enemy.gd").unwrap()];
+        assert_eq!(enemy_edits.len(), 2);
+        assert!(enemy_edits.iter().all(|edit| edit.new_text == "Hero"));
+    }
+
+    #[test]
+    fn rename_class_name_skips_shadowing_variables() {
+        let player = "class_name Player
+extends Node";
+        let enemy = "extends Node
+var target: Player
+func spawn():
+\tvar Player = 1
+\tprint(Player)";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/player.gd", player.to_string())
+            .unwrap();
+        filedb
+            .file_opened("/project/enemy.gd", enemy.to_string())
+            .unwrap();
+        let classdb = ClassDatabase::default();
+        classdb.update_class("/project/player.gd", &parse_file(player).unwrap(), player);
+
+        let edit = rename(
+            "/project/enemy.gd",
+            Position::new(1, 14),
+            "Hero",
+            &TEST_TYPEDB,
+            &filedb,
+            &classdb,
+            None,
+        )
+        .unwrap();
+        let changes = edit.changes.unwrap();
+        let enemy_edits = &changes[&Url::from_file_path("/project/enemy.gd").unwrap()];
+        assert_eq!(enemy_edits.len(), 1);
+        assert_eq!(enemy_edits[0].range.start, Position::new(1, 12));
+    }
+}
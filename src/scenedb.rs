@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use parking_lot::RwLock;
+
+/// Node trees of the workspace scenes keyed by the path of the script attached to a scene node.
+#[derive(Default, Clone)]
+pub struct SceneDatabase {
+    pub(crate) scenes: Arc<RwLock<HashMap<String, Vec<SceneNode>>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode {
+    /// Path relative to the node the script is attached to, e.g. `Body/Sprite`
+    pub path: String,
+    pub ttype: Option<String>,
+}
+
+impl SceneNode {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn parent_path(&self) -> &str {
+        self.path.rsplit_once('/').map_or("", |(parent, _)| parent)
+    }
+}
+
+impl SceneDatabase {
+    /// Reads all scenes of the workspace, returns number of scene files
+    pub fn scan_workspace(&self, workspace_root: &Path) -> usize {
+        self.scan_directory(workspace_root, workspace_root)
+    }
+
+    fn scan_directory(&self, workspace_root: &Path, directory: &Path) -> usize {
+        let mut scenes = 0;
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return scenes;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if path.is_dir() {
+                if !is_hidden {
+                    scenes += self.scan_directory(workspace_root, &path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "tscn") {
+                let Ok(content) = std::fs::read_to_string(&path) else {
+                    continue;
+                };
+                self.update_scene(workspace_root, &content);
+                scenes += 1;
+            }
+        }
+        scenes
+    }
+
+    /// Parses `.tscn` content and stores its nodes for the attached script
+    pub fn update_scene(&self, workspace_root: &Path, content: &str) {
+        let Some((script, nodes)) = parse_scene(content) else {
+            return;
+        };
+        let script_path = resolve_res_path(workspace_root, &script);
+        self.scenes
+            .write()
+            .insert(script_path.to_string_lossy().to_string(), nodes);
+    }
+
+    /// Returns direct children of the node at `parent_path` in the scene of the script,
+    /// empty `parent_path` means the node with the script itself
+    pub fn child_nodes(&self, script_path: &str, parent_path: &str) -> Vec<SceneNode> {
+        let lock = self.scenes.read();
+        let Some(nodes) = lock.get(script_path) else {
+            return Vec::new();
+        };
+        nodes
+            .iter()
+            .filter(|node| node.parent_path() == parent_path)
+            .cloned()
+            .collect()
+    }
+}
+
+pub fn resolve_res_path(workspace_root: &Path, path: &str) -> PathBuf {
+    match path.strip_prefix("res://") {
+        Some(relative) => workspace_root.join(relative),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Returns `res://` path of the first script attached to a node and all nodes below that node
+fn parse_scene(content: &str) -> Option<(String, Vec<SceneNode>)> {
+    let mut scripts = HashMap::new();
+    // scene relative node paths with their types in the order of declaration
+    let mut nodes: Vec<(String, Option<String>)> = Vec::new();
+    let mut script_node = None;
+    let mut in_node_section = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("[ext_resource") {
+            in_node_section = false;
+            if header_attribute(line, "type") == Some("Script") {
+                let (Some(id), Some(path)) =
+                    (header_attribute(line, "id"), header_attribute(line, "path"))
+                else {
+                    continue;
+                };
+                scripts.insert(id, path);
+            }
+        } else if line.starts_with("[node") {
+            in_node_section = true;
+            let Some(name) = header_attribute(line, "name") else {
+                in_node_section = false;
+                continue;
+            };
+            let path = match header_attribute(line, "parent") {
+                None => ".".to_string(),
+                Some(".") => name.to_string(),
+                Some(parent) => format!("{parent}/{name}"),
+            };
+            let ttype = header_attribute(line, "type").map(str::to_string);
+            nodes.push((path, ttype));
+        } else if line.starts_with('[') {
+            in_node_section = false;
+        } else if in_node_section && script_node.is_none() {
+            let Some(id) = line
+                .strip_prefix("script = ExtResource(\"")
+                .and_then(|rest| rest.split('"').next())
+            else {
+                continue;
+            };
+            let Some(script) = scripts.get(id) else {
+                continue;
+            };
+            let (node_path, _) = nodes.last()?;
+            script_node = Some((script.to_string(), node_path.clone()));
+        }
+    }
+
+    let (script, script_node_path) = script_node?;
+    let nodes = nodes
+        .into_iter()
+        .filter_map(|(path, ttype)| {
+            let path = if script_node_path == "." {
+                (path != ".").then_some(path)?
+            } else {
+                path.strip_prefix(&format!("{script_node_path}/"))?
+                    .to_string()
+            };
+            Some(SceneNode { path, ttype })
+        })
+        .collect();
+    Some((script, nodes))
+}
+
+/// Returns value of `key="value"` in a section header like `[node name="Player" type="Node3D"]`
+fn header_attribute<'l>(line: &'l str, key: &str) -> Option<&'l str> {
+    let pattern = format!(" {key}=\"");
+    let start = line.find(&pattern)? + pattern.len();
+    let end = line[start..].find('"')? + start;
+    Some(&line[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SceneNode, parse_scene};
+
+    #[test]
+    fn nodes_are_relative_to_script_node() {
+        let scene = "[gd_scene load_steps=2 format=3]
+
+[ext_resource type=\"Script\" path=\"res://player.gd\" id=\"1_abc\"]
+
+[node name=\"Level\" type=\"Node3D\"]
+
+[node name=\"Player\" type=\"CharacterBody3D\" parent=\".\"]
+script = ExtResource(\"1_abc\")
+
+[node name=\"Sprite\" type=\"Sprite3D\" parent=\"Player\"]
+
+[node name=\"Light\" type=\"OmniLight3D\" parent=\"Player/Sprite\"]
+
+[node name=\"Camera\" type=\"Camera3D\" parent=\".\"]
+";
+        let (script, nodes) = parse_scene(scene).unwrap();
+        assert_eq!(script, "res://player.gd");
+        assert_eq!(
+            nodes,
+            vec![
+                SceneNode {
+                    path: "Sprite".to_string(),
+                    ttype: Some("Sprite3D".to_string())
+                },
+                SceneNode {
+                    path: "Sprite/Light".to_string(),
+                    ttype: Some("OmniLight3D".to_string())
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+use tree_sitter::{Node, Point};
+
+use crate::{
+    extract_into_function::function_separator_lines,
+    filedb::FileDatabase,
+    symbol_table::extends_type,
+    typedb::{SymbolType, TypeDatabase},
+    utils::{node_content, point_to_position, uri_to_path},
+};
+
+/// The action rewrites the whole script, so it's offered only when the client asks for it
+pub const SORT_MEMBERS: &str = "source.sortMembers";
+
+/// Top level statement with the comments and annotations attached to it
+struct Member {
+    order: usize,
+    /// Members are sorted only within the sections separated by export groups
+    section: usize,
+    /// Standalone `@export_group` and alike, it stays first in its section
+    is_boundary: bool,
+    start_byte: usize,
+    end_byte: usize,
+    start: Point,
+    end: Point,
+}
+
+impl Member {
+    fn is_block(&self) -> bool {
+        self.order >= 6
+    }
+}
+
+/// Reorders class members into constants, enums, exported variables, variables,
+/// virtual methods and other methods, comments above a member move with it.
+/// Export groups and categories split the members into sections sorted separately,
+/// so exported variables stay in the group the author put them in
+pub fn sort_members_action(
+    params: &CodeActionParams,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    let requested = params.context.only.as_ref()?.iter().any(|kind| {
+        SORT_MEMBERS == kind.as_str() || SORT_MEMBERS.starts_with(&format!("{}.", kind.as_str()))
+    });
+    if !requested {
+        return None;
+    }
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+    let root = source_file.tree.root_node();
+
+    let mut cursor = root.walk();
+    let parent = root
+        .children(&mut cursor)
+        .find_map(|child| extends_type(child, &file))
+        .unwrap_or(SymbolType::Object("RefCounted".to_string()));
+    let mut members: Vec<Member> = Vec::new();
+    let mut attached: Option<Node> = None;
+    let mut is_exported = false;
+    let mut section = 0;
+    let mut cursor = root.walk();
+    for child in root.named_children(&mut cursor) {
+        let content = node_content(&child, &file);
+        if child.kind() == "comment" {
+            // a comment on the line of the previous member belongs to it
+            if let Some(last) = members
+                .last_mut()
+                .filter(|last| last.end.row == child.start_position().row)
+            {
+                last.end_byte = child.end_byte();
+                last.end = child.end_position();
+            } else {
+                attached.get_or_insert(child);
+            }
+            continue;
+        }
+        let is_annotation = matches!(child.kind(), "annotation" | "annotations");
+        if is_annotation && is_section_annotation(content) {
+            section += 1;
+            let start = attached.take().unwrap_or(child);
+            members.push(Member {
+                order: 4,
+                section,
+                is_boundary: true,
+                start_byte: start.start_byte(),
+                end_byte: child.end_byte(),
+                start: start.start_position(),
+                end: child.end_position(),
+            });
+            continue;
+        }
+        if is_annotation && !is_header_annotation(content) {
+            attached.get_or_insert(child);
+            is_exported |= content.starts_with("@export");
+            continue;
+        }
+        let order = match child.kind() {
+            "extends_statement" | "class_name_statement" | "annotation" | "annotations" => 0,
+            "signal_statement" => 1,
+            "const_statement" => 2,
+            "enum_definition" => 3,
+            "variable_statement" if is_exported || content.starts_with("@export") => 4,
+            "variable_statement" => 5,
+            "constructor_definition" => 6,
+            "function_definition" => {
+                let name = node_content(&child.child_by_field_name("name")?, &file);
+                let is_virtual =
+                    name.starts_with('_') && typedb.get_callable(&parent, name).is_some();
+                if is_virtual { 6 } else { 7 }
+            }
+            "class_definition" => 8,
+            // statements the order isn't known for are left where the author put them
+            _ => return None,
+        };
+        let start = attached.take().unwrap_or(child);
+        is_exported = false;
+        members.push(Member {
+            order,
+            section,
+            is_boundary: false,
+            start_byte: start.start_byte(),
+            end_byte: child.end_byte(),
+            start: start.start_position(),
+            end: child.end_position(),
+        });
+    }
+    let (first, last) = (members.first()?, members.last()?);
+    let range = Range::new(point_to_position(first.start), point_to_position(last.end));
+
+    let mut sorted = members.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|member| (member.section, !member.is_boundary, member.order));
+    if sorted
+        .iter()
+        .zip(&members)
+        .all(|(a, b)| a.start_byte == b.start_byte)
+    {
+        return None;
+    }
+    let function_gap = "\n".repeat(function_separator_lines(root, &file) + 1);
+    let mut new_text = String::new();
+    for (index, member) in sorted.iter().enumerate() {
+        if let Some(previous) = index.checked_sub(1).map(|index| sorted[index]) {
+            new_text.push_str(if previous.is_block() || member.is_block() {
+                function_gap.as_str()
+            } else if member.is_boundary {
+                "\n\n"
+            } else if previous.is_boundary {
+                "\n"
+            } else if previous.order != member.order {
+                "\n\n"
+            } else {
+                "\n"
+            });
+        }
+        new_text.push_str(&file[member.start_byte..member.end_byte]);
+    }
+
+    let edit = TextEdit::new(range, new_text);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Sort class members".to_string(),
+        kind: Some(CodeActionKind::new(SORT_MEMBERS)),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Standalone annotations grouping the exported variables that follow them in the inspector
+fn is_section_annotation(content: &str) -> bool {
+    ["@export_group", "@export_subgroup", "@export_category"]
+        .iter()
+        .any(|annotation| content.starts_with(annotation))
+}
+
+/// Script-wide annotations stay at the top together with `extends`
+fn is_header_annotation(content: &str) -> bool {
+    ["@tool", "@icon", "@static_unload"]
+        .iter()
+        .any(|annotation| content.starts_with(annotation))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{TEST_TYPEDB, apply_action, code_action_params, test_filedb};
+
+    use super::{SORT_MEMBERS, sort_members_action};
+
+    fn sort(file: &str, only: Option<Vec<CodeActionKind>>) -> Option<String> {
+        let mut params = code_action_params(Range::default());
+        params.context.only = only;
+        let action = sort_members_action(&params, &TEST_TYPEDB, &test_filedb(file));
+        apply_action(file, action.as_ref())
+    }
+
+    #[test]
+    fn members_are_sorted_in_conventional_order() {
+        let file = "extends Node
+
+# speed of the player
+var speed = 10
+
+
+func foo():
+\tpass
+
+
+func _ready():
+\tpass
+
+const MAX = 5 # limit
+@export var health = 3
+";
+        assert_eq!(sort(file, None), None);
+        assert_eq!(
+            sort(file, Some(vec![CodeActionKind::new(SORT_MEMBERS)])).unwrap(),
+            "extends Node
+
+const MAX = 5 # limit
+
+@export var health = 3
+
+# speed of the player
+var speed = 10
+
+
+func _ready():
+\tpass
+
+
+func foo():
+\tpass
+"
+        );
+    }
+
+    #[test]
+    fn export_groups_split_members_into_sections() {
+        let file = "extends Node
+
+var plain = 1
+@export var first = 1
+
+# movement settings
+@export_group(\"Movement\")
+const SPEED = 2
+@export var speed = 10
+var velocity = 0
+@export var accel = 2
+";
+        assert_eq!(
+            sort(file, Some(vec![CodeActionKind::new(SORT_MEMBERS)])).unwrap(),
+            "extends Node
+
+@export var first = 1
+
+var plain = 1
+
+# movement settings
+@export_group(\"Movement\")
+const SPEED = 2
+
+@export var speed = 10
+@export var accel = 2
+
+var velocity = 0
+"
+        );
+    }
+}
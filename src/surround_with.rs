@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+
+use crate::{
+    extract_into_function::{calculate_previous_indent_size, nodes_from_range},
+    filedb::FileDatabase,
+    utils::{point_to_position, uri_to_path},
+};
+
+/// Headers of the blocks selected statements can be wrapped in
+const SURROUND_BLOCKS: [&str; 3] = ["if true:", "for i in range():", "while true:"];
+
+/// Offers to wrap the selected statements into `if`, `for` or `while` block
+pub fn surround_with_actions(
+    params: &CodeActionParams,
+    filedb: &FileDatabase,
+) -> Vec<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let Some(source_file) = lock.get(&uri_to_path(uri)) else {
+        return Vec::new();
+    };
+    if params.range.start == params.range.end {
+        return Vec::new();
+    }
+    let file = source_file.content.to_string();
+    let Some(nodes) = nodes_from_range(source_file.tree.root_node(), params.range, &file) else {
+        return Vec::new();
+    };
+    let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+        return Vec::new();
+    };
+
+    let mut start_byte = first.start_byte();
+    while start_byte > 0 && file.as_bytes()[start_byte - 1] != b'\n' {
+        start_byte -= 1;
+    }
+    let content = &file[start_byte..last.end_byte()];
+    let indent = "\t".repeat(calculate_previous_indent_size(content));
+    let indented_content = content
+        .lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                line.to_string()
+            } else {
+                format!("\t{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let range = Range::new(
+        Position::new(first.start_position().row as u32, 0),
+        point_to_position(last.end_position()),
+    );
+
+    SURROUND_BLOCKS
+        .iter()
+        .map(|header| {
+            let edit = TextEdit::new(range, format!("{indent}{header}\n{indented_content}"));
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Surround with `{header}`"),
+                kind: Some(CodeActionKind::REFACTOR),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{apply_action, code_action_params, test_filedb};
+
+    use super::surround_with_actions;
+
+    #[test]
+    fn surround_two_statements_with_if() {
+        let file = "func foo():
+\tvar a = 10
+\tprint(a)
+\treturn a";
+        let params = code_action_params(Range::new(Position::new(1, 1), Position::new(2, 9)));
+        let actions = surround_with_actions(&params, &test_filedb(file));
+        assert_eq!(actions.len(), 3);
+        assert_eq!(
+            apply_action(file, actions.first()).unwrap(),
+            "func foo():
+\tif true:
+\t\tvar a = 10
+\t\tprint(a)
+\treturn a"
+        );
+    }
+}
@@ -1,18 +1,54 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Range};
 
 use async_lsp::lsp_types::{InlayHintKind, Position};
 use tree_sitter::{Node, Tree};
 
 use crate::{
-    typedb::{MethodInfo, SymbolType, TypeDatabase, VariantType},
-    utils::{node_content, parse_file, point_to_position},
+    cancellation::CancellationToken,
+    perf::PERF_TARGET,
+    typedb::{GLOBAL_SCOPES, MethodInfo, SymbolType, TypeDatabase, VariantType},
+    utils::{
+        annotation_name, declaration_annotations, identifier_at, node_content, parse_file,
+        point_to_position, walk_tree,
+    },
 };
 
 // TODO: @GDScript (range, print functions etc)
 
+/// Global functions returning the common type of their arguments, declared as `Variant`
+const NUMERIC_ARGUMENT_FUNCTIONS: [&str; 5] = ["min", "max", "clamp", "abs", "sign"];
+
+/// Operators testing any values, they are missing from the operator tables of the classes
+const BOOL_OPERATORS: [&str; 6] = ["in", "not in", "and", "or", "&&", "||"];
+
+/// Default of `SymbolTable::max_inference_depth`, only generated code gets anywhere close
+const DEFAULT_MAX_INFERENCE_DEPTH: usize = 256;
+
 pub struct SymbolTable<'a> {
     pub map: HashMap<usize, Scope>,
+    /// Expressions nested deeper than this are left uninferred instead of overflowing the stack
+    pub max_inference_depth: usize,
+    root_scope: usize,
+    /// When set only functions overlapping these byte ranges are analyzed
+    build_ranges: Option<Vec<Range<usize>>>,
     class_parent: Option<SymbolType>,
+    /// Declared or inferred return types of the functions defined in the file
+    function_returns: HashMap<String, SymbolType>,
+    /// Byte ranges of the functions without a return type annotation whose return values
+    /// weren't inferred yet, they are inferred only once a built scope calls them
+    pending_functions: HashMap<String, Range<usize>>,
+    /// Return values of a function that isn't built are being inferred, calls in them
+    /// have no scope that could hold their parameter hints
+    inferring_returns: bool,
+    /// Script is annotated with `@tool` and runs in the editor
+    is_tool: bool,
+    /// Building stops early once the request the table is built for is cancelled
+    cancellation: CancellationToken,
+    /// Number of `infer_type` calls currently on the stack
+    inference_depth: usize,
+    /// Byte ranges of array and dictionary literals assigned to constants, keyed by
+    /// `hint_position` of the constant, so subscripts with a literal index can be folded
+    constant_values: HashMap<Position, Range<usize>>,
     typedb: &'a TypeDatabase,
 }
 
@@ -21,6 +57,8 @@ pub struct Scope {
     pub id: usize,
     pub parent: usize,
     pub vars: Vec<Symbol>,
+    /// Scope is a body of a static function or is nested in one
+    pub is_static: bool,
 }
 
 impl Scope {
@@ -29,6 +67,7 @@ impl Scope {
             id: node.id(),
             parent: parent_scope,
             vars: Vec::new(),
+            is_static: false,
         }
     }
 }
@@ -39,7 +78,16 @@ pub struct Symbol {
     pub byte: usize,
     pub hint_position: Position,
     pub static_typed: bool,
+    /// Declared type if the symbol is statically typed, inferred type otherwise
     pub ttype: Option<SymbolType>,
+    /// Type inferred from the assigned value, kept even if the declaration has a type annotation
+    pub inferred_type: Option<SymbolType>,
+    /// Constants and `static var`s belong to the class and can be used in static functions
+    pub is_static: bool,
+    /// Declared in the parameter list of a function
+    pub is_parameter: bool,
+    /// `TYPE` for declarations including function parameters, `PARAMETER` for the names
+    /// of call arguments shown before the passed values
     pub kind: InlayHintKind,
 }
 
@@ -47,44 +95,195 @@ impl<'a> SymbolTable<'a> {
     pub fn new(typedb: &'a TypeDatabase) -> Self {
         Self {
             map: HashMap::new(),
+            max_inference_depth: DEFAULT_MAX_INFERENCE_DEPTH,
+            root_scope: 0,
+            build_ranges: None,
             class_parent: None,
+            function_returns: HashMap::new(),
+            pending_functions: HashMap::new(),
+            inferring_returns: false,
+            is_tool: false,
+            cancellation: CancellationToken::default(),
+            inference_depth: 0,
+            constant_values: HashMap::new(),
             typedb,
         }
     }
 
+    pub fn set_cancellation(&mut self, cancellation: CancellationToken) {
+        self.cancellation = cancellation;
+    }
+
     pub fn build_table(&mut self, tree: &Tree, file: &str) {
+        let _span = tracing::debug_span!(target: PERF_TARGET, "build_table").entered();
         let root = tree.root_node();
+        // calls can come before the called function or be recursive,
+        // so functions are collected before any body is walked
+        self.collect_functions(root, file);
         let new_scope_id = self.insert_new_scope(root, 0);
+        self.root_scope = new_scope_id;
         self.build_body(root, file);
         for symbol in &mut self.map.get_mut(&new_scope_id).unwrap().vars {
             symbol.byte = 0
         }
     }
 
+    /// Builds table for class level declarations and only those functions that overlap
+    /// with the given byte ranges, so a localized edit or a small visible range doesn't
+    /// require analysis of every function in the file
+    pub fn build_table_in_ranges(&mut self, tree: &Tree, file: &str, ranges: Vec<Range<usize>>) {
+        self.build_ranges = Some(ranges);
+        self.build_table(tree, file);
+    }
+
+    /// Fills return types of the script functions from their annotations, functions without
+    /// one are left for `function_return_type`, so a build limited to a few ranges doesn't
+    /// infer the return values of every function in the file
+    fn collect_functions(&mut self, root: Node, file: &str) {
+        let mut cursor = root.walk();
+        let members = root.named_children(&mut cursor).collect::<Vec<_>>();
+        if let Some(parent) = members
+            .iter()
+            .find_map(|member| extends_type(*member, file))
+        {
+            self.class_parent = Some(parent);
+        }
+        for function in members
+            .iter()
+            .filter(|member| member.kind() == "function_definition")
+        {
+            let Some(name_node) = function.child_by_field_name("name") else {
+                continue;
+            };
+            let name = node_content(&name_node, file).to_string();
+            match function.child_by_field_name("return_type") {
+                Some(type_node) => {
+                    let return_type = SymbolType::from_str(node_content(&type_node, file));
+                    self.function_returns.insert(name, return_type);
+                }
+                None => {
+                    self.pending_functions.insert(name, function.byte_range());
+                }
+            }
+        }
+    }
+
+    /// Returns declared or inferred return type of the script function, return values of
+    /// functions that aren't built yet get the types that don't depend on locals. The result
+    /// is memoized and functions built later refine it with their locals
+    fn function_return_type(&mut self, call: Node, name: &str, file: &str) -> Option<SymbolType> {
+        if let Some(return_type) = self.function_returns.get(name) {
+            return Some(return_type.clone());
+        }
+        // removed before inference, so recursive calls and functions whose return values
+        // can't be inferred are only visited once
+        let range = self.pending_functions.remove(name)?;
+        let mut root = call;
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        let body = root
+            .descendant_for_byte_range(range.start, range.end)?
+            .child_by_field_name("body")?;
+        let inferring_returns = std::mem::replace(&mut self.inferring_returns, true);
+        let return_type = self.infer_return_type(body, file);
+        self.inferring_returns = inferring_returns;
+        if let Some(return_type) = &return_type {
+            self.function_returns
+                .insert(name.to_string(), return_type.clone());
+        }
+        return_type
+    }
+
+    fn should_build(&self, node: Node) -> bool {
+        let Some(ranges) = &self.build_ranges else {
+            return true;
+        };
+        ranges
+            .iter()
+            .any(|range| range.start <= node.end_byte() && node.start_byte() <= range.end)
+    }
+
     pub fn insert_new_scope(&mut self, body: Node, parent_scope: usize) -> usize {
-        let new_scope = Scope::new(body, parent_scope);
+        let mut new_scope = Scope::new(body, parent_scope);
+        new_scope.is_static = self
+            .map
+            .get(&parent_scope)
+            .is_some_and(|scope| scope.is_static);
         let id = new_scope.id;
         self.map.insert(id, new_scope);
         id
     }
 
+    /// Returns method of the parent class the function overrides, e.g. `Node._process`
+    fn overridden_method(&self, function: Node, file: &str) -> Option<&'a MethodInfo> {
+        let typedb = self.typedb;
+        let name = node_content(&function.child_by_field_name("name")?, file);
+        let (owner, method) = typedb.get_callable_with_owner(self.class_parent.as_ref()?, name)?;
+        (!GLOBAL_SCOPES.contains(&owner.to_string().as_str())).then_some(method)
+    }
+
+    /// Keeps `vars` of the scope ordered by `byte`, lookups stop at the first symbol declared
+    /// after the position, but values can be inferred after later declarations were inserted,
+    /// e.g. return values are inferred once the whole function body is built
+    fn insert_symbol(&mut self, scope_id: usize, symbol: Symbol) {
+        let Some(scope) = self.map.get_mut(&scope_id) else {
+            return;
+        };
+        let index = scope.vars.partition_point(|var| var.byte <= symbol.byte);
+        scope.vars.insert(index, symbol);
+    }
+
     pub fn build_body(&mut self, body: Node, file: &str) {
         let current_scope_id = body.id();
         let mut cursor = body.walk();
         for child in body.children(&mut cursor) {
+            if self.cancellation.is_cancelled() {
+                return;
+            }
             match child.kind() {
                 "variable_statement" | "const_statement" => {
                     let name_node = child.child_by_field_name("name").unwrap();
                     let name = node_content(&name_node, file);
-                    let value_node = child.child_by_field_name("value");
-                    let type_node = child.child_by_field_name("type");
-                    let mut static_typed = false;
-                    let mut ttype = None;
-                    if let Some(type_node) = type_node {
-                        ttype = Some(SymbolType::from_str(node_content(&type_node, file)));
-                        static_typed = true;
-                    } else if let Some(value_node) = value_node {
-                        ttype = self.infer_type(current_scope_id, value_node, file)
+                    let mut value_node = child.child_by_field_name("value");
+                    // `var a = b = 0` assigns to `b` and declares `a` with the same value
+                    while let Some(assignment) =
+                        value_node.filter(|node| node.kind() == "assignment")
+                    {
+                        self.refine_assigned_symbol(current_scope_id, assignment, file);
+                        value_node = assignment
+                            .child_by_field_name("right")
+                            .or(assignment.child(2));
+                    }
+                    // `:=` infers the type from the value, so it's not an explicit annotation
+                    let type_node = child
+                        .child_by_field_name("type")
+                        .filter(|type_node| !is_inferred_type_operator(*type_node, file));
+                    let inferred_type = value_node
+                        .and_then(|value_node| self.infer_type(current_scope_id, value_node, file));
+                    let static_typed = type_node.is_some();
+                    let ttype = match type_node {
+                        Some(type_node) => {
+                            Some(SymbolType::from_str(node_content(&type_node, file)))
+                        }
+                        // `@export_flags` and its `_2d_physics` like variants are bitmasks
+                        None => inferred_type.clone().or_else(|| {
+                            declaration_annotations(child)
+                                .iter()
+                                .any(|annotation| {
+                                    annotation_name(*annotation, file).starts_with("export_flags")
+                                })
+                                .then_some(SymbolType::Variant(VariantType::Int))
+                        }),
+                    };
+                    if let Some(value_node) = value_node.filter(|node| {
+                        child.kind() == "const_statement"
+                            && matches!(node.kind(), "array" | "dictionary")
+                    }) {
+                        self.constant_values.insert(
+                            point_to_position(name_node.end_position()),
+                            value_node.byte_range(),
+                        );
                     }
                     let symbol = Symbol {
                         name: name.to_string(),
@@ -92,48 +291,86 @@ impl<'a> SymbolTable<'a> {
                         hint_position: point_to_position(name_node.end_position()),
                         static_typed,
                         ttype,
+                        inferred_type,
+                        is_static: child.kind() == "const_statement" || has_static_keyword(child),
+                        is_parameter: false,
                         kind: InlayHintKind::TYPE,
                     };
-                    self.map
-                        .get_mut(&current_scope_id)
-                        .unwrap()
-                        .vars
-                        .push(symbol)
+                    self.insert_symbol(current_scope_id, symbol);
                 }
                 "function_definition" => {
-                    let body_node = child.child_by_field_name("body").unwrap();
+                    if !self.should_build(child) {
+                        continue;
+                    }
+                    let Some(body_node) = child.child_by_field_name("body") else {
+                        continue;
+                    };
                     let new_scope_id = self.insert_new_scope(body_node, current_scope_id);
+                    if has_static_keyword(child) {
+                        self.map.get_mut(&new_scope_id).unwrap().is_static = true;
+                    }
 
                     if let Some(parameters) = child.child_by_field_name("parameters") {
                         let function_begins = body_node.start_byte();
-                        for i in 0..parameters.child_count() {
-                            let parameter = parameters.child(i).unwrap();
-                            if parameter.kind() == "typed_parameter" {
-                                let name_node = parameter.child(0).unwrap();
-                                let name = node_content(&name_node, file);
-                                let type_node = parameter.child_by_field_name("type");
-                                let mut ttype = None;
-                                if let Some(type_node) = type_node {
-                                    ttype =
-                                        Some(SymbolType::from_str(node_content(&type_node, file)));
+                        let overridden = self.overridden_method(child, file);
+                        let mut cursor = parameters.walk();
+                        for (index, parameter) in parameters.named_children(&mut cursor).enumerate()
+                        {
+                            let (name_node, static_typed) = match parameter.kind() {
+                                "typed_parameter" => (parameter.child(0).unwrap(), true),
+                                "identifier" => (parameter, false),
+                                _ => continue,
+                            };
+                            let name = node_content(&name_node, file);
+                            let ttype = match parameter.child_by_field_name("type") {
+                                Some(type_node) => {
+                                    Some(SymbolType::from_str(node_content(&type_node, file)))
                                 }
-                                let symbol = Symbol {
-                                    name: name.to_string(),
-                                    byte: function_begins,
-                                    hint_position: point_to_position(name_node.end_position()),
-                                    static_typed: true,
-                                    ttype,
-                                    kind: InlayHintKind::TYPE,
-                                };
-                                self.map.get_mut(&new_scope_id).unwrap().vars.push(symbol)
-                            }
+                                // untyped parameters of overridden virtual methods like
+                                // `_process(delta)` have the types declared by the engine
+                                None => overridden
+                                    .and_then(|method| method.parameters.get(index))
+                                    .map(|parameter| parameter.ttype.clone()),
+                            };
+                            let symbol = Symbol {
+                                name: name.to_string(),
+                                byte: function_begins,
+                                hint_position: point_to_position(name_node.end_position()),
+                                static_typed,
+                                ttype,
+                                inferred_type: None,
+                                is_static: false,
+                                is_parameter: true,
+                                kind: InlayHintKind::TYPE,
+                            };
+                            self.insert_symbol(new_scope_id, symbol);
                         }
                     }
 
                     self.build_body(body_node, file);
+
+                    let return_type = match child.child_by_field_name("return_type") {
+                        Some(type_node) => {
+                            Some(SymbolType::from_str(node_content(&type_node, file)))
+                        }
+                        None => self.infer_return_type(body_node, file),
+                    };
+                    if let Some(name_node) = child.child_by_field_name("name") {
+                        let name = node_content(&name_node, file);
+                        self.pending_functions.remove(name);
+                        if let Some(return_type) = return_type {
+                            self.function_returns.insert(name.to_string(), return_type);
+                        }
+                    }
                 }
                 "if_statement" => {
-                    let body_node = child.child_by_field_name("body").unwrap();
+                    // conditions can't declare variables, but calls in them still get hints
+                    if let Some(condition) = child.child_by_field_name("condition") {
+                        self.infer_type(current_scope_id, condition, file);
+                    }
+                    let Some(body_node) = child.child_by_field_name("body") else {
+                        continue;
+                    };
                     self.insert_new_scope(body_node, current_scope_id);
                     self.build_body(body_node, file);
 
@@ -141,34 +378,132 @@ impl<'a> SymbolTable<'a> {
                     let mut cursor = child.walk();
                     let alternatives = child.children_by_field_name("alternative", &mut cursor);
                     for elif_clause in alternatives {
-                        let body_node = elif_clause.child_by_field_name("body").unwrap();
+                        if let Some(condition) = elif_clause.child_by_field_name("condition") {
+                            self.infer_type(current_scope_id, condition, file);
+                        }
+                        let Some(body_node) = elif_clause.child_by_field_name("body") else {
+                            continue;
+                        };
                         self.insert_new_scope(body_node, current_scope_id);
                         self.build_body(body_node, file);
                     }
                 }
-                "elif_clause" | "else_clause" | "for_statement" => {
-                    let body_node = child.child_by_field_name("body").unwrap();
+                "elif_clause" | "else_clause" => {
+                    let Some(body_node) = child.child_by_field_name("body") else {
+                        continue;
+                    };
                     self.insert_new_scope(body_node, current_scope_id);
                     self.build_body(body_node, file);
                 }
+                "while_statement" => {
+                    if let Some(condition) = child.child_by_field_name("condition") {
+                        self.infer_type(current_scope_id, condition, file);
+                    }
+                    let Some(body_node) = child.child_by_field_name("body") else {
+                        continue;
+                    };
+                    self.insert_new_scope(body_node, current_scope_id);
+                    self.build_body(body_node, file);
+                }
+                "for_statement" => {
+                    let Some(body_node) = child.child_by_field_name("body") else {
+                        continue;
+                    };
+                    let new_scope_id = self.insert_new_scope(body_node, current_scope_id);
+                    if let Some(name_node) = child.child_by_field_name("left") {
+                        let type_node = child.child_by_field_name("type");
+                        let ttype = match type_node {
+                            Some(type_node) => {
+                                Some(SymbolType::from_str(node_content(&type_node, file)))
+                            }
+                            None => child
+                                .child_by_field_name("right")
+                                .and_then(|iterable| {
+                                    self.infer_type(current_scope_id, iterable, file)
+                                })
+                                .and_then(|iterable_type| iterable_type.iterated_type()),
+                        };
+                        let symbol = Symbol {
+                            name: node_content(&name_node, file).to_string(),
+                            byte: body_node.start_byte(),
+                            hint_position: point_to_position(name_node.end_position()),
+                            static_typed: type_node.is_some(),
+                            ttype,
+                            inferred_type: None,
+                            is_static: false,
+                            is_parameter: false,
+                            kind: InlayHintKind::TYPE,
+                        };
+                        self.insert_symbol(new_scope_id, symbol);
+                    }
+                    self.build_body(body_node, file);
+                }
+                "annotation" | "annotations" if current_scope_id == self.root_scope => {
+                    let content = node_content(&child, file);
+                    if content == "@tool" || content.starts_with("@tool ") {
+                        self.is_tool = true;
+                    }
+                }
                 "extends_statement" => {
-                    let type_node = child.child(1);
-                    let mut ttype = None;
-                    if let Some(type_node) = type_node {
-                        ttype = Some(SymbolType::from_str(node_content(&type_node, file)));
+                    self.class_parent = extends_type(child, file);
+                }
+                "class_name_statement" => {
+                    if let Some(parent) = extends_type(child, file) {
+                        self.class_parent = Some(parent);
                     }
-                    self.class_parent = ttype;
                 }
                 "match_statement" => {
                     let Some(match_body) = child.child_by_field_name("body") else {
                         continue;
                     };
+                    let value_type = child
+                        .child_by_field_name("value")
+                        .and_then(|value| self.infer_type(current_scope_id, value, file));
                     let mut cursor = match_body.walk();
                     for pattern_section in match_body.children(&mut cursor) {
                         let Some(pattern_body) = pattern_section.child_by_field_name("body") else {
                             continue;
                         };
-                        self.insert_new_scope(pattern_body, current_scope_id);
+                        // `var x` bindings are visible in both the `when` guard and the body,
+                        // so they live in a scope of the whole section
+                        let section_scope_id =
+                            self.insert_new_scope(pattern_section, current_scope_id);
+                        let mut bindings = Vec::new();
+                        walk_tree(pattern_section, &mut |node| {
+                            if node.kind() == "pattern_binding"
+                                && node.start_byte() < pattern_body.start_byte()
+                            {
+                                bindings.extend(node.named_child(0));
+                            }
+                        });
+                        for name_node in bindings {
+                            let symbol = Symbol {
+                                name: node_content(&name_node, file).to_string(),
+                                byte: pattern_section.start_byte(),
+                                hint_position: point_to_position(name_node.end_position()),
+                                static_typed: false,
+                                // bindings nested in array or dictionary patterns hold elements
+                                ttype: (!is_destructured(name_node, pattern_section))
+                                    .then(|| value_type.clone())
+                                    .flatten(),
+                                inferred_type: None,
+                                is_static: false,
+                                is_parameter: false,
+                                kind: InlayHintKind::TYPE,
+                            };
+                            self.insert_symbol(section_scope_id, symbol);
+                        }
+                        let mut section_cursor = pattern_section.walk();
+                        let guards = pattern_section
+                            .children(&mut section_cursor)
+                            .filter(|node| node.kind() == "pattern_guard")
+                            .collect::<Vec<_>>();
+                        for guard in guards {
+                            if let Some(condition) = guard.named_child(0) {
+                                self.infer_type(section_scope_id, condition, file);
+                            }
+                        }
+                        self.insert_new_scope(pattern_body, section_scope_id);
                         self.build_body(pattern_body, file);
                     }
                 }
@@ -176,20 +511,74 @@ impl<'a> SymbolTable<'a> {
                     let Some(inner) = child.child(0) else {
                         continue;
                     };
-                    self.infer_type(current_scope_id, inner, file);
+                    if inner.kind() == "assignment" {
+                        self.refine_assigned_symbol(current_scope_id, inner, file);
+                    } else {
+                        self.infer_type(current_scope_id, inner, file);
+                    }
                 }
                 _ => (),
             }
         }
     }
 
+    /// Sets type of a symbol declared without type from the first assignment to it,
+    /// statically typed declarations are left untouched. In a chain like `a = b = 0`
+    /// every target gets the type of the innermost value
+    fn refine_assigned_symbol(&mut self, scope_id: usize, assignment: Node, file: &str) {
+        let mut targets = Vec::new();
+        let mut value = assignment;
+        while value.kind() == "assignment" {
+            let (Some(left), Some(right)) = (
+                value.child_by_field_name("left").or(value.child(0)),
+                value.child_by_field_name("right").or(value.child(2)),
+            ) else {
+                return;
+            };
+            targets.push(left);
+            value = right;
+        }
+        let ttype = self.infer_type(scope_id, value, file);
+        for left in targets {
+            if left.kind() != "identifier" {
+                continue;
+            }
+            let name = node_content(&left, file);
+            let Some((symbol_scope, index)) = self.find_symbol(scope_id, name, left.start_byte())
+            else {
+                continue;
+            };
+            let symbol = &mut self.map.get_mut(&symbol_scope).unwrap().vars[index];
+            if !symbol.static_typed && symbol.ttype.is_none() {
+                symbol.ttype = ttype.clone();
+            }
+        }
+    }
+
     pub fn infer_type(&mut self, scope_id: usize, node: Node, file: &str) -> Option<SymbolType> {
-        let _position = node.start_byte();
+        if self.inference_depth >= self.max_inference_depth {
+            return None;
+        }
+        self.inference_depth += 1;
+        let ttype = self.infer_node_type(scope_id, node, file);
+        self.inference_depth -= 1;
+        ttype
+    }
+
+    fn infer_node_type(&mut self, scope_id: usize, node: Node, file: &str) -> Option<SymbolType> {
         match node.kind() {
             "integer" => Some(SymbolType::Variant(VariantType::Int)),
             "float" => Some(SymbolType::Variant(VariantType::Float)),
             "false" | "true" => Some(SymbolType::Variant(VariantType::Bool)),
             "string" => Some(SymbolType::Variant(VariantType::String)),
+            // `&"name"` and `^"path"` literals
+            "string_name" => Some(SymbolType::Variant(VariantType::String_name)),
+            "node_path" => Some(SymbolType::Variant(VariantType::Node_path)),
+            // both `$Path` and scene unique `%Name` are parsed as `get_node`,
+            // scenes aren't known here so the exact node type can't be resolved
+            "get_node" => Some(SymbolType::Object("Node".to_string())),
+            // members accessed through `super.` resolve on the parent class
+            "super" => self.class_parent.clone(),
             "binary_operator" => self.infer_binary_operator_type(scope_id, node, file),
             "identifier" => self.infer_identifier_type(scope_id, node, file),
             "attribute" => self.infer_attribute_type(scope_id, node, file),
@@ -198,6 +587,74 @@ impl<'a> SymbolTable<'a> {
                 self.infer_parenthesized_expression_type(scope_id, node, file)
             }
             "unary_operator" => self.infer_unary_operator_type(scope_id, node, file),
+            "array" => self.infer_array_type(scope_id, node, file),
+            // dictionary literals are never typed, `Dictionary[K, V]` comes only from annotations
+            "dictionary" => Some(SymbolType::Variant(VariantType::Dictionary)),
+            "subscript" => {
+                if let Some(element) = self.folded_constant_element(scope_id, node, file) {
+                    if let Some(ttype) = self.infer_type(scope_id, element, file) {
+                        return Some(ttype);
+                    }
+                }
+                let container = node.named_child(0)?;
+                self.infer_type(scope_id, container, file)?.element_type()
+            }
+            // awaiting a coroutine call results in the value returned from it
+            "await_expression" => {
+                let awaited = node.named_child(node.named_child_count().checked_sub(1)?)?;
+                self.infer_type(scope_id, awaited, file)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns element of a constant literal subscripted with a literal index,
+    /// e.g. `1.5` for `SPEEDS[1]` after `const SPEEDS = [1, 1.5]`
+    fn folded_constant_element<'t>(
+        &self,
+        scope_id: usize,
+        subscript: Node<'t>,
+        file: &str,
+    ) -> Option<Node<'t>> {
+        let container = subscript.named_child(0)?;
+        let index = subscript.named_child(1)?;
+        if container.kind() != "identifier" {
+            return None;
+        }
+        let name = node_content(&container, file);
+        let (symbol_scope, symbol_index) =
+            self.find_symbol(scope_id, name, container.start_byte())?;
+        let symbol = &self.map.get(&symbol_scope)?.vars[symbol_index];
+        let value_range = self.constant_values.get(&symbol.hint_position)?;
+
+        let mut root = subscript;
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        let mut value = root.descendant_for_byte_range(value_range.start, value_range.end)?;
+        while !matches!(value.kind(), "array" | "dictionary") || value.byte_range() != *value_range
+        {
+            value = value.parent()?;
+        }
+        let mut cursor = value.walk();
+        let mut elements = value
+            .named_children(&mut cursor)
+            .filter(|element| element.kind() != "comment");
+        let index_text = node_content(&index, file);
+        match (value.kind(), index.kind()) {
+            ("array", "integer") => elements.nth(index_text.parse().ok()?),
+            ("dictionary", "string" | "integer") => elements.find_map(|pair| {
+                let key = pair.named_child(0)?;
+                let key_text = node_content(&key, file);
+                // `{speed = 1.0}` keys are strings named like the identifier
+                let matches_key = if key.kind() == "identifier" {
+                    index_text.trim_matches(['"', '\'']) == key_text
+                } else {
+                    index_text == key_text
+                };
+                let value = pair.named_child(pair.named_child_count().checked_sub(1)?)?;
+                (matches_key && value.id() != key.id()).then_some(value)
+            }),
             _ => None,
         }
     }
@@ -208,52 +665,118 @@ impl<'a> SymbolTable<'a> {
         node: Node,
         file: &str,
     ) -> Option<SymbolType> {
-        let lhs_node = node.child(0).unwrap();
-        let name = node_content(&lhs_node, file);
-        let is_class;
-        let type_info = if lhs_node.kind() == "parenthesized_expression" {
-            // TODO: doesn't support parenthesized class names like "(Input).get_vector..."
-            is_class = false;
-            let paren_expr_type =
-                self.infer_parenthesized_expression_type(scope_id, lhs_node, file)?;
-            self.typedb.classes.get(&paren_expr_type)?
-        } else if let Some(ttype) = self.get_symbol_type(scope_id, name, lhs_node.start_byte()) {
-            is_class = false;
-            self.typedb.classes.get(ttype)?
+        let lhs_node = node.child(0)?;
+        // the chain starts either from a class like `Input.get_vector()` or from the type of
+        // any expression like a variable or a call in `get_viewport().get_camera_3d().fov`
+        let (mut ttype, mut is_class) = if lhs_node.kind() == "identifier" {
+            let name = node_content(&lhs_node, file);
+            match self.get_symbol_type(scope_id, name, lhs_node.start_byte()) {
+                Some(ttype) => (ttype.clone(), false),
+                None => (SymbolType::from_str(name), true),
+            }
         } else {
-            is_class = true;
-            self.typedb.classes.get(&SymbolType::from_str(name))?
+            (self.infer_type(scope_id, lhs_node, file)?, false)
         };
-        let attribute_node = node.child(2).unwrap();
-        match attribute_node.kind() {
+        let mut cursor = node.walk();
+        let members = node
+            .children(&mut cursor)
+            .skip(1)
+            .filter(|child| child.kind() != ".")
+            .collect::<Vec<_>>();
+        for member in members {
+            ttype = self.infer_member_type(scope_id, &ttype, is_class, member, file)?;
+            is_class = false;
+        }
+        Some(ttype)
+    }
+
+    /// Infers type of a property, constant or method call accessed on a value of `ttype`,
+    /// members inherited from ancestors are included
+    fn infer_member_type(
+        &mut self,
+        scope_id: usize,
+        ttype: &SymbolType,
+        is_class: bool,
+        member: Node,
+        file: &str,
+    ) -> Option<SymbolType> {
+        let typedb = self.typedb;
+        let class = ttype.untyped();
+        match member.kind() {
             "identifier" => {
-                let field_name = node_content(&attribute_node, file);
+                let field_name = node_content(&member, file);
                 if is_class {
-                    let constant = type_info.constants.get(field_name)?;
-                    // TODO: optimize this, currently we are parsing small value string like "Vector3(0.0, 0.0, 0.0)" using tree-sitter
-                    // each time we want to infer type of constant like Vector3.ZERO
-                    let parsed = parse_file(&constant.value)?;
-                    let expression_statement = parsed.root_node().child(0)?;
-                    let expression = expression_statement.child(0)?;
-                    self.infer_type(0, expression, &constant.value)
+                    let constant = typedb.classes.get(&class)?.constants.get(field_name)?;
+                    self.infer_constant_type(&constant.value)
+                } else if let Some(property_type) = typedb.get_property_type(&class, field_name) {
+                    Some(property_type.clone())
                 } else {
-                    let field_info = type_info.properties.get(field_name)?;
-                    Some(field_info.ttype.clone())
+                    // `node.queue_free` without a call is a method reference
+                    typedb
+                        .get_callable(&class, field_name)
+                        .map(|_| SymbolType::Variant(VariantType::Callable))
                 }
             }
             "attribute_call" => {
-                let method_name_node = attribute_node.child(0).unwrap();
-                let method_name = node_content(&method_name_node, file);
-                let method_info = type_info.methods.get(method_name)?;
-                if let Some(arguments) = attribute_node.child_by_field_name("arguments") {
+                let method_name = node_content(&member.child(0)?, file);
+                let method_info = std::iter::once(&class)
+                    .chain(typedb.inheritance_chain(&class))
+                    .find_map(|class| typedb.classes.get(class)?.methods.get(method_name))?;
+                let arguments = member.child_by_field_name("arguments");
+                if let Some(arguments) = arguments {
                     self.add_parameter_hints(scope_id, arguments, method_info);
                 }
+                // `Dictionary.get` is declared as `Variant`, but values of a typed dictionary
+                // and the default in `get("key", 0)` tell what is expected to be stored
+                if class == SymbolType::Variant(VariantType::Dictionary) && method_name == "get" {
+                    if let SymbolType::Dictionary(_, value_type) = ttype {
+                        return Some(value_type.as_ref().clone());
+                    }
+                    if let Some(default) = arguments.and_then(|arguments| arguments.named_child(1))
+                    {
+                        return self.infer_type(scope_id, default, file);
+                    }
+                }
                 Some(method_info.return_type.clone())
             }
-            _ => unreachable!(),
+            _ => None,
         }
     }
 
+    /// Reconciles types of all values returned from the function body into their common type,
+    /// `None` is returned if the body has no valued returns or one of them can't be inferred
+    fn infer_return_type(&mut self, body: Node, file: &str) -> Option<SymbolType> {
+        let mut values = Vec::new();
+        collect_return_values(body, &mut values);
+        let mut return_type: Option<SymbolType> = None;
+        for value in values {
+            let value_type = self.infer_type(self.scope_of(value), value, file)?;
+            return_type = Some(match return_type {
+                Some(ttype) => self.typedb.common_type(&ttype, &value_type),
+                None => value_type,
+            });
+        }
+        return_type
+    }
+
+    fn infer_constant_type(&mut self, value: &str) -> Option<SymbolType> {
+        // TODO: optimize this, currently we are parsing small value string like "Vector3(0.0, 0.0, 0.0)" using tree-sitter
+        // each time we want to infer type of constant like Vector3.ZERO
+        let parsed = parse_file(value)?;
+        let expression_statement = parsed.root_node().child(0)?;
+        let expression = expression_statement.child(0)?;
+        // values like "Basis(1, 0, 0, 0, 1, 0, 0, 0, 1)" list every component and don't match
+        // any constructor, but their type is always the type of the called class
+        if expression.kind() == "call" {
+            let class_name = node_content(&expression.child(0)?, value);
+            let ttype = SymbolType::from_str(class_name);
+            if self.typedb.classes.contains_key(&ttype) {
+                return Some(ttype);
+            }
+        }
+        self.infer_type(0, expression, value)
+    }
+
     pub fn infer_binary_operator_type(
         &mut self,
         scope_id: usize,
@@ -262,6 +785,11 @@ impl<'a> SymbolTable<'a> {
     ) -> Option<SymbolType> {
         let left_node = bin_op.child_by_field_name("left").unwrap();
         let right_node = bin_op.child_by_field_name("right").unwrap();
+        // keyword operators like `not in` can span several tokens
+        let op_text = file[left_node.end_byte()..right_node.start_byte()].trim();
+        if BOOL_OPERATORS.contains(&op_text) {
+            return Some(SymbolType::Variant(VariantType::Bool));
+        }
         let left_type = self.infer_type(scope_id, left_node, file)?;
         let right_type = self.infer_type(scope_id, right_node, file)?;
         // TODO: should we use `node_content` instead of relying on the fact that kind is equal to operator character ?
@@ -271,27 +799,152 @@ impl<'a> SymbolTable<'a> {
             .cloned()
     }
 
+    pub fn root_scope(&self) -> usize {
+        self.root_scope
+    }
+
+    pub fn is_tool(&self) -> bool {
+        self.is_tool
+    }
+
+    pub fn class_parent(&self) -> Option<&SymbolType> {
+        self.class_parent.as_ref()
+    }
+
+    /// Returns variables declared before `position` that are visible from `scope`,
+    /// variables of inner scopes come first
+    pub fn visible_symbols(&self, mut scope: usize, position: usize) -> Vec<&Symbol> {
+        let mut symbols: Vec<&Symbol> = Vec::new();
+        while let Some(cur_scope) = self.map.get(&scope) {
+            for var in &cur_scope.vars {
+                if var.byte >= position {
+                    break;
+                }
+                if var.kind == InlayHintKind::TYPE && symbols.iter().all(|s| s.name != var.name) {
+                    symbols.push(var);
+                }
+            }
+            scope = cur_scope.parent;
+        }
+        symbols
+    }
+
+    /// Checks if the symbol is declared at the class level, i.e. is a member of the script
+    pub fn is_member(&self, symbol: &Symbol) -> bool {
+        self.map
+            .get(&self.root_scope)
+            .is_some_and(|scope| scope.vars.iter().any(|var| std::ptr::eq(var, symbol)))
+    }
+
+    /// Returns id of the innermost scope containing the node
+    pub fn scope_of(&self, node: Node) -> usize {
+        let mut cur_node = Some(node);
+        while let Some(node) = cur_node {
+            if self.map.contains_key(&node.id()) {
+                return node.id();
+            }
+            cur_node = node.parent();
+        }
+        0
+    }
+
+    /// Returns scope id and index of the variable whose declaration or usage is at the position,
+    /// the cursor can also be right after the identifier
+    pub fn symbol_id_at(
+        &self,
+        root: Node,
+        position: Position,
+        file: &str,
+    ) -> Option<(usize, usize)> {
+        let node = identifier_at(root, position)?;
+        self.resolve_symbol(node, file)
+    }
+
+    /// Returns the variable whose declaration or usage is at the position
+    pub fn symbol_at(&self, root: Node, position: Position, file: &str) -> Option<&Symbol> {
+        let (scope_id, index) = self.symbol_id_at(root, position, file)?;
+        self.map.get(&scope_id)?.vars.get(index)
+    }
+
+    /// Returns scope id and index of the variable declared or referenced by the identifier,
+    /// `self.member` accesses are resolved to the class member
+    pub fn resolve_symbol(&self, node: Node, file: &str) -> Option<(usize, usize)> {
+        let name = node_content(&node, file);
+        let hint_position = point_to_position(node.end_position());
+        for (scope_id, scope) in &self.map {
+            let declared = scope.vars.iter().position(|var| {
+                var.kind == InlayHintKind::TYPE
+                    && var.name == name
+                    && var.hint_position == hint_position
+            });
+            if let Some(index) = declared {
+                return Some((*scope_id, index));
+            }
+        }
+
+        let parent = node.parent()?;
+        match parent.kind() {
+            "attribute" if parent.child(0) != Some(node) => {
+                let lhs = parent.child(0)?;
+                let is_self_member = node_content(&lhs, file) == "self"
+                    && node.prev_sibling().and_then(|dot| dot.prev_sibling()) == Some(lhs);
+                if !is_self_member {
+                    return None;
+                }
+                self.find_symbol(self.root_scope, name, usize::MAX)
+            }
+            "attribute_call" | "type" => None,
+            _ => self.find_symbol(self.scope_of(node), name, node.start_byte()),
+        }
+    }
+
+    /// Returns symbol declared by the `name` node of a variable statement or a parameter
+    pub fn declared_symbol(&self, name_node: Node, file: &str) -> Option<&Symbol> {
+        let name = node_content(&name_node, file);
+        let hint_position = point_to_position(name_node.end_position());
+        self.map.values().find_map(|scope| {
+            scope.vars.iter().find(|var| {
+                var.kind == InlayHintKind::TYPE
+                    && var.name == name
+                    && var.hint_position == hint_position
+            })
+        })
+    }
+
+    /// Returns scope id and index of the variable declared before `position` and visible from `scope`
+    fn find_symbol(
+        &self,
+        mut scope: usize,
+        symbol: &str,
+        position: usize,
+    ) -> Option<(usize, usize)> {
+        while let Some(cur_scope) = self.map.get(&scope) {
+            for (index, var) in cur_scope.vars.iter().enumerate() {
+                if var.byte >= position {
+                    break;
+                }
+                if var.kind == InlayHintKind::TYPE && var.name == symbol {
+                    return Some((scope, index));
+                }
+            }
+            scope = cur_scope.parent;
+        }
+        None
+    }
+
     pub fn get_symbol_type(
         &self,
         scope: usize,
         symbol: &str,
         position: usize,
     ) -> Option<&SymbolType> {
-        if scope == 0 {
-            if let Some(parent) = &self.class_parent {
-                return self.typedb.get_property_type(parent, symbol);
-            }
+        // parameter hints live in the same scopes as variables, so lookup must skip them,
+        // otherwise a hint named like a constant declared earlier shadows it
+        if let Some((scope, index)) = self.find_symbol(scope, symbol, position) {
+            return self.map.get(&scope)?.vars[index].ttype.as_ref();
         }
-        let scope = self.map.get(&scope)?;
-        for var in &scope.vars {
-            if var.byte >= position {
-                break;
-            }
-            if var.name == symbol {
-                return var.ttype.as_ref();
-            }
-        }
-        self.get_symbol_type(scope.parent, symbol, position)
+        let parent = self.class_parent.as_ref()?;
+        self.typedb.get_property_type(parent, symbol)
     }
 
     fn infer_identifier_type(
@@ -301,13 +954,34 @@ impl<'a> SymbolTable<'a> {
         file: &str,
     ) -> Option<SymbolType> {
         let name = node_content(&identifier, file);
-        self.get_symbol_type(scope_id, name, identifier.start_byte())
-            .cloned()
+        if let Some(ttype) = self.get_symbol_type(scope_id, name, identifier.start_byte()) {
+            return Some(ttype.clone());
+        }
+        // a method name used as a value like `button.pressed.connect(on_pressed)`
+        let mut root = identifier;
+        while let Some(parent) = root.parent() {
+            root = parent;
+        }
+        let is_script_function = root.children(&mut root.walk()).any(|child| {
+            child.kind() == "function_definition"
+                && child
+                    .child_by_field_name("name")
+                    .is_some_and(|function_name| node_content(&function_name, file) == name)
+        });
+        let is_inherited_method = self
+            .class_parent
+            .as_ref()
+            .is_some_and(|parent| self.typedb.get_callable(parent, name).is_some());
+        (is_script_function || is_inherited_method)
+            .then_some(SymbolType::Variant(VariantType::Callable))
     }
 
     fn infer_call_type(&mut self, scope_id: usize, node: Node, file: &str) -> Option<SymbolType> {
         let name_node = node.child(0).unwrap();
         let name = node_content(&name_node, file);
+        if name_node.kind() == "super" {
+            return self.infer_super_call_type(node, file);
+        }
 
         // If function names is equal to the name of one of the registered classes
         // then get constructor's return type
@@ -326,32 +1000,76 @@ impl<'a> SymbolTable<'a> {
             }
         }
 
-        // TODO: get local defined methods first
-        if let Some(parent) = &self.class_parent {
-            let callable = self.typedb.get_callable(parent, name);
-            if let Some(callable) = callable {
-                if let Some(arguments) = node.child_by_field_name("arguments") {
-                    self.add_parameter_hints(scope_id, arguments, callable);
-                }
+        if let Some(return_type) = self.function_return_type(node, name, file) {
+            return Some(return_type);
+        }
 
-                let infered_type = &callable.return_type;
-                if infered_type == &SymbolType::Object("Variant".to_string()) {
-                    if let Some(arguments) = node.child_by_field_name("arguments") {
-                        if let Some(first_child) = arguments.child(1) {
-                            return self.infer_type(scope_id, first_child, file);
-                        }
-                    }
+        // declared as returning an untyped `Array`, but the elements are always ints
+        if name == "range" {
+            return Some(SymbolType::Array(VariantType::Int));
+        }
+
+        // `max(1, 2)` is an int while `max(1, 2.5)` is a float
+        if NUMERIC_ARGUMENT_FUNCTIONS.contains(&name) {
+            let arguments = node.child_by_field_name("arguments")?;
+            if let Some(callable) = self
+                .typedb
+                .get_callable(&SymbolType::Object(GLOBAL_SCOPES[0].to_string()), name)
+            {
+                self.add_parameter_hints(scope_id, arguments, callable);
+            }
+            let argument_types = arguments
+                .named_children(&mut arguments.walk())
+                .map(|argument| self.infer_type(scope_id, argument, file))
+                .collect::<Option<Vec<_>>>()?;
+            let (first, rest) = argument_types.split_first()?;
+            return Some(rest.iter().fold(first.clone(), |common, ttype| {
+                self.typedb.common_type(&common, ttype)
+            }));
+        }
+
+        // scripts without `extends` are `RefCounted`, global functions are available either way
+        let parent = self
+            .class_parent
+            .clone()
+            .unwrap_or_else(|| SymbolType::Object("RefCounted".to_string()));
+        let callable = self.typedb.get_callable(&parent, name)?;
+        if let Some(arguments) = node.child_by_field_name("arguments") {
+            self.add_parameter_hints(scope_id, arguments, callable);
+        }
+
+        let infered_type = &callable.return_type;
+        if infered_type == &SymbolType::Object("Variant".to_string()) {
+            if let Some(arguments) = node.child_by_field_name("arguments") {
+                if let Some(first_child) = arguments.child(1) {
+                    return self.infer_type(scope_id, first_child, file);
                 }
-                Some(infered_type.clone())
-            } else {
-                None
             }
-        } else {
-            None
         }
+        Some(infered_type.clone())
+    }
+
+    /// `super()` calls the parent's implementation of the enclosing function,
+    /// in `_init` it's the parent constructor which has no value
+    fn infer_super_call_type(&mut self, call: Node, file: &str) -> Option<SymbolType> {
+        let mut function = call.parent()?;
+        while function.kind() != "function_definition" {
+            function = function.parent()?;
+        }
+        let name = node_content(&function.child_by_field_name("name")?, file);
+        if name == "_init" {
+            return None;
+        }
+        let method = self
+            .typedb
+            .get_callable(self.class_parent.as_ref()?, name)?;
+        Some(method.return_type.clone())
     }
 
     fn add_parameter_hints(&mut self, scope_id: usize, arguments: Node, method: &MethodInfo) {
+        if self.inferring_returns {
+            return;
+        }
         for (arg_node, param) in arguments
             .named_children(&mut arguments.walk())
             .zip(method.parameters.iter())
@@ -363,11 +1081,12 @@ impl<'a> SymbolTable<'a> {
                 hint_position: position,
                 static_typed: false,
                 ttype: None,
+                inferred_type: None,
+                is_static: false,
+                is_parameter: false,
                 kind: InlayHintKind::PARAMETER,
             };
-            if let Some(scope) = self.map.get_mut(&scope_id) {
-                scope.vars.push(symbol);
-            }
+            self.insert_symbol(scope_id, symbol);
         }
     }
 
@@ -381,6 +1100,33 @@ impl<'a> SymbolTable<'a> {
         self.infer_type(scope_id, inner_expression, file)
     }
 
+    /// Array literal with elements of the same type is typed as `Array[T]`
+    fn infer_array_type(&mut self, scope_id: usize, node: Node, file: &str) -> Option<SymbolType> {
+        let untyped_array = SymbolType::Variant(VariantType::Array);
+        let element_types = node
+            .named_children(&mut node.walk())
+            .filter(|element| element.kind() != "comment")
+            .map(|element| self.infer_type(scope_id, element, file))
+            .collect::<Vec<_>>();
+        let Some(Some(first_type)) = element_types.first() else {
+            return Some(untyped_array);
+        };
+        if element_types
+            .iter()
+            .any(|element_type| element_type.as_ref() != Some(first_type))
+        {
+            return Some(untyped_array);
+        }
+        match first_type {
+            SymbolType::Variant(VariantType::Array) => Some(untyped_array),
+            SymbolType::Variant(variant_type) => Some(SymbolType::Array(*variant_type)),
+            SymbolType::Object(class_name) if class_name != "Variant" => {
+                Some(SymbolType::ObjectArray(class_name.clone()))
+            }
+            _ => Some(untyped_array),
+        }
+    }
+
     fn infer_unary_operator_type(
         &mut self,
         scope_id: usize,
@@ -388,28 +1134,83 @@ impl<'a> SymbolTable<'a> {
         file: &str,
     ) -> Option<SymbolType> {
         let inner_expression = node.child(1)?;
+        // logical negation isn't in the operator table since it applies to any value
+        let op = node_content(&node.child(0)?, file);
+        if matches!(op, "not" | "!") {
+            return Some(SymbolType::Variant(VariantType::Bool));
+        }
         let inner_type = self.infer_type(scope_id, inner_expression, file)?;
-        let op = node.child(0)?.kind();
         self.typedb
             .get_unary_operator_type(&inner_type, op)
             .cloned()
     }
 }
 
+fn is_inferred_type_operator(type_node: Node, file: &str) -> bool {
+    type_node.kind() == "inferred_type" || node_content(&type_node, file) == ":="
+}
+
+/// Returns the parent class of an `extends` statement or of a combined
+/// `class_name Player extends CharacterBody3D` statement which nests the `extends`
+pub fn extends_type(statement: Node, file: &str) -> Option<SymbolType> {
+    let extends_statement = if statement.kind() == "extends_statement" {
+        statement
+    } else {
+        let mut cursor = statement.walk();
+        statement
+            .children(&mut cursor)
+            .find(|child| child.kind() == "extends_statement")?
+    };
+    let type_node = extends_statement.child(1)?;
+    Some(SymbolType::from_str(node_content(&type_node, file)))
+}
+
+/// Checks if the node is inside an array or dictionary pattern of the section
+fn is_destructured(node: Node, pattern_section: Node) -> bool {
+    let mut ancestor = node.parent();
+    while let Some(cur_node) = ancestor.filter(|cur_node| *cur_node != pattern_section) {
+        if cur_node.kind().contains("array") || cur_node.kind().contains("dictionary") {
+            return true;
+        }
+        ancestor = cur_node.parent();
+    }
+    false
+}
+
+/// Checks if a function or variable is declared with `static` keyword
+pub(crate) fn has_static_keyword(node: Node) -> bool {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .any(|child| matches!(child.kind(), "static_keyword" | "static"))
+}
+
+/// Collects values of `return` statements of the function body, nested functions are skipped
+fn collect_return_values<'t>(node: Node<'t>, values: &mut Vec<Node<'t>>) {
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "return_statement" => values.extend(child.named_child(0)),
+            "function_definition" | "lambda" => (),
+            _ => collect_return_values(child, values),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::sync::LazyLock;
+    use std::time::Instant;
 
-    use tree_sitter::Tree;
+    use async_lsp::lsp_types::{InlayHintKind, Position, TextDocumentContentChangeEvent};
+    use tree_sitter::{Point, Tree};
 
     use crate::{
-        typedb::{SymbolType, TypeDatabase, VariantType},
-        utils::parse_file,
+        classdb::collect_script_class,
+        filedb::FileDatabase,
+        test_utils::TEST_TYPEDB,
+        typedb::{SymbolType, VariantType},
+        utils::{parse_file, position_to_byte},
     };
 
-    static TEST_TYPEDB: LazyLock<TypeDatabase> =
-        LazyLock::new(|| TypeDatabase::from_file("./assets/type_info.json").unwrap());
-
     use super::SymbolTable;
 
     fn test_build_st(file: &str) -> (SymbolTable, Tree) {
@@ -423,17 +1224,21 @@ mod tests {
     /// Checks if variable in a function has specific type
     fn assert_var_type((st, tree): &(SymbolTable, Tree), var_name: &str, ty: SymbolType) {
         let root = tree.root_node();
-        let mut function_node = root.child(0).unwrap();
-        if function_node.kind() != "function_definition" {
-            function_node = root.child(1).unwrap();
-        }
-        let function_scope_id = function_node.child_by_field_name("body").unwrap().id();
-        let scope = st.map.get(&function_scope_id).unwrap();
-        for var in &scope.vars {
-            if var.name == var_name {
-                assert_eq!(var.ttype, Some(ty.clone()));
-            }
-        }
+        let function_node = root
+            .named_children(&mut root.walk())
+            .find(|child| child.kind() == "function_definition")
+            .expect("function not found");
+        // the variable can be declared in a nested block of the function
+        let var = st
+            .map
+            .values()
+            .filter(|scope| scope.id != st.root_scope)
+            .flat_map(|scope| &scope.vars)
+            .filter(|var| var.kind == InlayHintKind::TYPE && var.name == var_name)
+            .filter(|var| function_node.byte_range().contains(&var.byte))
+            .min_by_key(|var| var.byte)
+            .expect("var not found");
+        assert_eq!(var.ttype, Some(ty), "type of `{var_name}`");
     }
 
     #[test]
@@ -482,4 +1287,826 @@ func foo():
             SymbolType::Variant(VariantType::Transform3d),
         );
     }
+
+    #[test]
+    fn assignment_refines_untyped_variable() {
+        let file = "func foo():
+\tvar x
+\tvar typed: float
+\tx = 5
+\ttyped = 5
+\tvar y = x";
+        let st = test_build_st(file);
+        assert_var_type(&st, "x", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "typed", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "y", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn top_level_const_referencing_previous_const() {
+        let file = "const A = 10
+const B = A + 5";
+        let (st, tree) = test_build_st(file);
+        let root_scope = st.map.get(&tree.root_node().id()).unwrap();
+        let b = root_scope.vars.iter().find(|var| var.name == "B").unwrap();
+        assert_eq!(b.ttype, Some(SymbolType::Variant(VariantType::Int)));
+    }
+
+    #[test]
+    fn local_const_referencing_previous_const() {
+        let file = "func foo():
+\tconst a = 1.5
+\tconst b = max(1, 2)
+\tconst c = a * 2";
+        let st = test_build_st(file);
+        assert_var_type(&st, "c", SymbolType::Variant(VariantType::Float));
+    }
+
+    #[test]
+    fn assign_color_constants_and_constructors() {
+        let file = "func foo():
+\tvar red = Color.RED
+\tvar named = Color(\"red\")
+\tvar identity = Basis.IDENTITY";
+        let st = test_build_st(file);
+        assert_var_type(&st, "red", SymbolType::Variant(VariantType::Color));
+        assert_var_type(&st, "named", SymbolType::Variant(VariantType::Color));
+        assert_var_type(&st, "identity", SymbolType::Variant(VariantType::Basis));
+    }
+
+    #[test]
+    fn annotated_variable_keeps_inferred_type() {
+        let file = "func foo():
+\tvar x: float = 5";
+        let (st, tree) = test_build_st(file);
+        let function_scope_id = tree
+            .root_node()
+            .child(0)
+            .unwrap()
+            .child_by_field_name("body")
+            .unwrap()
+            .id();
+        let x = &st.map.get(&function_scope_id).unwrap().vars[0];
+        assert!(x.static_typed);
+        assert_eq!(x.ttype, Some(SymbolType::Variant(VariantType::Float)));
+        assert_eq!(x.inferred_type, Some(SymbolType::Variant(VariantType::Int)));
+    }
+
+    #[test]
+    fn build_only_functions_in_ranges() {
+        let mut file = "var member = 1.5\n".to_string();
+        for i in 0..50 {
+            file += &format!("func foo{i}():\n\tvar a = member * {i}\n\n");
+        }
+        let tree = parse_file(&file).unwrap();
+
+        let mut full = SymbolTable::new(&TEST_TYPEDB);
+        full.build_table(&tree, &file);
+        assert_eq!(full.map.len(), 51);
+
+        let edited_function = tree.root_node().child(11).unwrap();
+        let edit_byte = edited_function.start_byte() + 5;
+        let mut partial = SymbolTable::new(&TEST_TYPEDB);
+        partial.build_table_in_ranges(&tree, &file, vec![edit_byte..edit_byte + 1]);
+        assert_eq!(partial.map.len(), 2);
+
+        let body_id = edited_function.child_by_field_name("body").unwrap().id();
+        let a = &partial.map.get(&body_id).unwrap().vars[0];
+        assert_eq!(a.ttype, Some(SymbolType::Variant(VariantType::Float)));
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_localized_edit() {
+        const ITERATIONS: u32 = 20;
+        let mut file = "var member = 1.5\n".to_string();
+        for i in 0..2000 {
+            file += &format!("func foo{i}():\n\tvar a = member * {i}\n\treturn a\n\n");
+        }
+        let filedb = FileDatabase::default();
+        filedb.file_opened("/bench.gd", file).unwrap();
+        // a statement typed in the middle of the file
+        let position = Position::new(4003, 1);
+        filedb.file_changed(
+            "/bench.gd",
+            vec![TextDocumentContentChangeEvent {
+                range: Some(async_lsp::lsp_types::Range::new(position, position)),
+                range_length: None,
+                text: "var b = a\n\t".to_string(),
+            }],
+        );
+        let lock = filedb.files.read();
+        let source_file = &lock["/bench.gd"];
+        let content = source_file.content.to_string();
+        let edit_byte = position_to_byte(&content, position);
+
+        let measure = |ranges: Option<Vec<std::ops::Range<usize>>>| {
+            let start = Instant::now();
+            let mut scopes = 0;
+            for _ in 0..ITERATIONS {
+                let mut st = SymbolTable::new(&TEST_TYPEDB);
+                match ranges.clone() {
+                    Some(ranges) => st.build_table_in_ranges(&source_file.tree, &content, ranges),
+                    None => st.build_table(&source_file.tree, &content),
+                }
+                scopes = st.map.len();
+            }
+            (start.elapsed() / ITERATIONS, scopes)
+        };
+        let (full_time, full_scopes) = measure(None);
+        let (edit_time, edit_scopes) = measure(Some(vec![edit_byte..edit_byte + 1]));
+        eprintln!(
+            "full build: {full_time:?} for {full_scopes} scopes, \
+             localized edit: {edit_time:?} for {edit_scopes} scopes"
+        );
+        assert_eq!(full_scopes, 2001);
+        assert_eq!(edit_scopes, 2);
+    }
+
+    #[test]
+    fn pass_only_bodies_keep_parameter_scope() {
+        let file = "func foo(x: int): pass
+func bar(y: float):
+\tbreakpoint";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        for (index, name, ty) in [(0, "x", VariantType::Int), (1, "y", VariantType::Float)] {
+            let function = root.named_child(index).unwrap();
+            let body = function.child_by_field_name("body").unwrap();
+            let scope = st.map.get(&body.id()).unwrap();
+            assert_eq!(scope.vars.len(), 1);
+            assert_eq!(scope.vars[0].name, name);
+            assert_eq!(scope.vars[0].ttype, Some(SymbolType::Variant(ty)));
+        }
+    }
+
+    #[test]
+    fn return_types_are_reconciled() {
+        let file = "func widened(a):
+\tif a:
+\t\treturn 1
+\treturn 2.0
+func mixed(a):
+\tif a:
+\t\treturn 1
+\treturn \"one\"
+func foo():
+\tvar f = widened(true)
+\tvar v = mixed(true)";
+        let (st, tree) = test_build_st(file);
+        let foo = tree.root_node().named_child(2).unwrap();
+        let scope = st
+            .map
+            .get(&foo.child_by_field_name("body").unwrap().id())
+            .unwrap();
+        assert_eq!(
+            scope.vars[0].ttype,
+            Some(SymbolType::Variant(VariantType::Float))
+        );
+        assert_eq!(
+            scope.vars[1].ttype,
+            Some(SymbolType::Object("Variant".to_string()))
+        );
+    }
+
+    #[test]
+    fn if_branch_variables_are_not_visible_in_else() {
+        let file = "func foo(a):
+\tif a:
+\t\tvar x = 10
+\t\tprint(x)
+\telse:
+\t\tprint(x)";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        let if_x = root
+            .descendant_for_point_range(Point::new(3, 8), Point::new(3, 8))
+            .unwrap();
+        let else_x = root
+            .descendant_for_point_range(Point::new(5, 8), Point::new(5, 8))
+            .unwrap();
+        assert_eq!(
+            st.get_symbol_type(st.scope_of(if_x), "x", if_x.start_byte()),
+            Some(&SymbolType::Variant(VariantType::Int))
+        );
+        assert_ne!(st.scope_of(if_x), st.scope_of(else_x));
+        assert_eq!(
+            st.get_symbol_type(st.scope_of(else_x), "x", else_x.start_byte()),
+            None
+        );
+        assert_eq!(st.resolve_symbol(else_x, file), None);
+    }
+
+    #[test]
+    fn rpc_function_is_scoped_and_await_infers_through() {
+        let file = "extends Node
+@rpc func _do(x: int):
+\tvar tree = await get_tree()
+\tvar y = x";
+        let (st, tree) = test_build_st(file);
+        let mut function = None;
+        crate::utils::walk_tree(tree.root_node(), &mut |node| {
+            if node.kind() == "function_definition" {
+                function = Some(node);
+            }
+        });
+        let body = function.unwrap().child_by_field_name("body").unwrap();
+        let scope = st.map.get(&body.id()).unwrap();
+        let var_type = |name: &str| {
+            scope
+                .vars
+                .iter()
+                .find(|var| var.name == name)
+                .and_then(|var| var.ttype.clone())
+        };
+        assert_eq!(var_type("x"), Some(SymbolType::Variant(VariantType::Int)));
+        assert_eq!(
+            var_type("tree"),
+            Some(SymbolType::Object("SceneTree".to_string()))
+        );
+        assert_eq!(var_type("y"), Some(SymbolType::Variant(VariantType::Int)));
+    }
+
+    #[test]
+    fn numeric_literal_forms() {
+        let file = "func foo():
+\tvar hex = 0xFF
+\tvar bin = 0b1010
+\tvar big = 1_000_000
+\tvar f = 1_000.5
+\tvar e = 1e3";
+        let st = test_build_st(file);
+        assert_var_type(&st, "hex", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "bin", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "big", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "f", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "e", SymbolType::Variant(VariantType::Float));
+    }
+
+    #[test]
+    fn string_name_and_node_path_literals() {
+        let file = "func foo():
+\tvar action = &\"jump\"
+\tvar path = ^\"Player/Sprite\"";
+        let st = test_build_st(file);
+        assert_var_type(&st, "action", SymbolType::Variant(VariantType::String_name));
+        assert_var_type(&st, "path", SymbolType::Variant(VariantType::Node_path));
+        assert_eq!(
+            SymbolType::from_str("StringName"),
+            SymbolType::Variant(VariantType::String_name)
+        );
+    }
+
+    #[test]
+    fn mixed_arithmetic_chains() {
+        let file = "func foo():
+\tvar f = 1 + 2 * 3.0
+\tvar i = 1 + 2 + 3
+\tvar p = (1 + 2) * 3.0
+\tvar m = 2 ** 3 % 5";
+        let st = test_build_st(file);
+        assert_var_type(&st, "f", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "i", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "p", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "m", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn typed_dictionary_subscript() {
+        let file = "func foo():
+\tvar positions: Dictionary[String, Vector3] = {}
+\tvar position = positions[\"player\"]
+\tvar keys = positions.keys()";
+        let st = test_build_st(file);
+        assert_var_type(
+            &st,
+            "positions",
+            SymbolType::Dictionary(
+                Box::new(SymbolType::Variant(VariantType::String)),
+                Box::new(SymbolType::Variant(VariantType::Vector3)),
+            ),
+        );
+        assert_var_type(&st, "position", SymbolType::Variant(VariantType::Vector3));
+        assert_var_type(&st, "keys", SymbolType::Variant(VariantType::Array));
+        assert_eq!(
+            SymbolType::from_str("Dictionary[String, Vector3]").to_string(),
+            "Dictionary[String, Vector3]"
+        );
+    }
+
+    #[test]
+    fn dictionary_get_infers_default_type() {
+        let file = "func foo():
+\tvar stats = {}
+\tvar speed = stats.get(\"speed\", 1.5)
+\tvar anything = stats.get(\"speed\")
+\tvar positions: Dictionary[String, Vector3] = {}
+\tvar position = positions.get(\"player\")";
+        let st = test_build_st(file);
+        assert_var_type(&st, "speed", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "anything", SymbolType::Object("Variant".to_string()));
+        assert_var_type(&st, "position", SymbolType::Variant(VariantType::Vector3));
+    }
+
+    #[test]
+    fn scope_vars_are_in_source_order() {
+        let file = "var speed: float = 1.0
+func foo():
+\tvar copy = speed
+\tvar speed = \"fast\"
+\treturn Vector2(1.0, 2.0)
+\tvar after = 1";
+        let st = test_build_st(file);
+        assert_var_type(&st, "copy", SymbolType::Variant(VariantType::Float));
+        for scope in st.0.map.values() {
+            assert!(scope.vars.is_sorted_by_key(|var| var.byte));
+        }
+        let function_scope =
+            st.0.map
+                .values()
+                .find(|scope| scope.vars.len() > 1)
+                .unwrap();
+        let names = function_scope
+            .vars
+            .iter()
+            .map(|var| var.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["copy", "speed", "x", "y", "after"]);
+    }
+
+    #[test]
+    fn chained_method_calls() {
+        let file = "extends Node
+func foo():
+\tvar fov = get_viewport().get_camera_3d().fov
+\tvar camera_position = get_viewport().get_camera_3d().position";
+        let st = test_build_st(file);
+        assert_var_type(&st, "fov", SymbolType::Variant(VariantType::Float));
+        assert_var_type(
+            &st,
+            "camera_position",
+            SymbolType::Variant(VariantType::Vector3),
+        );
+    }
+
+    #[test]
+    fn chained_assignment_refines_every_target() {
+        let file = "func foo():
+\tvar a
+\tvar b
+\ta = b = 10";
+        let st = test_build_st(file);
+        assert_var_type(&st, "a", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "b", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn virtual_method_parameters_are_typed() {
+        let file = "extends Node
+func _process(delta):
+\tpass";
+        let (st, tree) = test_build_st(file);
+        let function = tree.root_node().child(1).unwrap();
+        let body_id = function.child_by_field_name("body").unwrap().id();
+        let delta = &st.map[&body_id].vars[0];
+        assert_eq!(delta.name, "delta");
+        assert_eq!(delta.ttype, Some(SymbolType::Variant(VariantType::Float)));
+        assert!(!delta.static_typed);
+    }
+
+    #[test]
+    fn combined_class_name_and_extends() {
+        let file = "class_name Player extends CharacterBody3D
+func foo():
+\tvar speed = velocity";
+        let (st, _) = test_build_st(file);
+        assert_eq!(
+            st.class_parent(),
+            Some(&SymbolType::Object("CharacterBody3D".to_string()))
+        );
+
+        let tree = parse_file(file).unwrap();
+        let (name, class) = collect_script_class("/player.gd", &tree, file).unwrap();
+        assert_eq!(name, "Player");
+        assert_eq!(
+            class.parent,
+            Some(SymbolType::Object("CharacterBody3D".to_string()))
+        );
+    }
+
+    #[test]
+    fn local_const_collections() {
+        let file = "func foo():
+\tconst SPEEDS = [1, 2, 3]
+\tconst NAMES = {\"a\": 1}
+\tconst FIRST = SPEEDS[0]";
+        let st = test_build_st(file);
+        assert_var_type(&st, "SPEEDS", SymbolType::Array(VariantType::Int));
+        assert_var_type(&st, "NAMES", SymbolType::Variant(VariantType::Dictionary));
+        assert_var_type(&st, "FIRST", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn node_traversal_methods_resolve_through_parents() {
+        let file = "extends CharacterBody3D
+func foo():
+\tvar paused = get_tree().paused
+\tvar parent_name = get_parent().name
+\tvar sibling = get_node_or_null(\"Sibling\")
+\tvar frame = get_parent().get_tree().get_frame()";
+        let st = test_build_st(file);
+        assert_var_type(&st, "paused", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(
+            &st,
+            "parent_name",
+            SymbolType::Variant(VariantType::String_name),
+        );
+        assert_var_type(&st, "sibling", SymbolType::Object("Node".to_string()));
+        assert_var_type(&st, "frame", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn for_loop_over_packed_array() {
+        let file = "func foo(names: PackedStringArray, points: PackedVector3Array):
+\tfor name in names:
+\t\tvar upper = name
+\tfor point in points:
+\t\tvar first = points[0]
+\t\tprint(point, first)";
+        let (st, tree) = test_build_st(file);
+        let scope_types = |row| {
+            let node = tree
+                .root_node()
+                .descendant_for_point_range(Point::new(row, 3), Point::new(row, 3))
+                .unwrap();
+            let scope = st.map.get(&st.scope_of(node)).unwrap();
+            scope
+                .vars
+                .iter()
+                .map(|var| (var.name.clone(), var.ttype.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            scope_types(2),
+            vec![
+                (
+                    "name".to_string(),
+                    Some(SymbolType::Variant(VariantType::String))
+                ),
+                (
+                    "upper".to_string(),
+                    Some(SymbolType::Variant(VariantType::String))
+                ),
+            ]
+        );
+        assert_eq!(
+            scope_types(4),
+            vec![
+                (
+                    "point".to_string(),
+                    Some(SymbolType::Variant(VariantType::Vector3))
+                ),
+                (
+                    "first".to_string(),
+                    Some(SymbolType::Variant(VariantType::Vector3))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn method_references_are_callables() {
+        let file = "extends Node
+func foo():
+\tvar constructed = Callable(self, \"foo\")
+\tvar script_method = foo
+\tvar inherited = queue_free
+\tvar attribute = get_tree().quit";
+        let st = test_build_st(file);
+        let callable = SymbolType::Variant(VariantType::Callable);
+        assert_var_type(&st, "constructed", callable.clone());
+        assert_var_type(&st, "script_method", callable.clone());
+        assert_var_type(&st, "inherited", callable.clone());
+        assert_var_type(&st, "attribute", callable);
+    }
+
+    #[test]
+    fn deeply_nested_expression_is_not_inferred() {
+        let shallow = vec!["1"; 10].join(" + ");
+        let deep = vec!["1"; 100].join(" + ");
+        let file = format!("func foo():\n\tvar shallow = {shallow}\n\tvar deep = {deep}");
+        let tree = parse_file(&file).unwrap();
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        st.max_inference_depth = 32;
+        st.build_table(&tree, &file);
+        let function_node = tree.root_node().child(0).unwrap();
+        let scope_id = function_node.child_by_field_name("body").unwrap().id();
+        let vars = &st.map[&scope_id].vars;
+        assert_eq!(vars[0].ttype, Some(SymbolType::Variant(VariantType::Int)));
+        assert_eq!(vars[1].ttype, None);
+
+        // the same chain fits into the default depth
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        st.build_table(&tree, &file);
+        assert_eq!(
+            st.map[&scope_id].vars[1].ttype,
+            Some(SymbolType::Variant(VariantType::Int))
+        );
+    }
+
+    #[test]
+    fn numeric_functions_follow_argument_types() {
+        let file = "func foo(x: float, v: Vector2):
+\tvar largest = max(1, 2)
+\tvar mixed = min(1, 2.5)
+\tvar clamped = clamp(x, 0.0, 1.0)
+\tvar length = abs(v)";
+        let st = test_build_st(file);
+        assert_var_type(&st, "largest", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "mixed", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "clamped", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "length", SymbolType::Variant(VariantType::Vector2));
+    }
+
+    #[test]
+    fn constant_literal_subscript_is_folded() {
+        let file = "func foo():
+\tconst STEPS = [0.5, 1.0]
+\tconst LIMITS = [10, 2.5, \"max\"]
+\tconst NAMES = {\"player\": \"Hero\", speed = 4.0}
+\tvar step = STEPS[0]
+\tvar count = LIMITS[0]
+\tvar limit = LIMITS[1]
+\tvar name = NAMES[\"player\"]
+\tvar speed = NAMES[\"speed\"]";
+        let st = test_build_st(file);
+        assert_var_type(&st, "step", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "count", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "limit", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "name", SymbolType::Variant(VariantType::String));
+        assert_var_type(&st, "speed", SymbolType::Variant(VariantType::Float));
+    }
+
+    #[test]
+    fn keyword_and_bitwise_unary_operators() {
+        let file = "func foo(flag, items: Array):
+\tvar mask := 6
+\tvar inverted = not flag
+\tvar empty = !items
+\tvar bits = ~mask
+\tvar negative = -mask";
+        let st = test_build_st(file);
+        assert_var_type(&st, "inverted", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(&st, "empty", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(&st, "bits", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "negative", SymbolType::Variant(VariantType::Int));
+    }
+
+    #[test]
+    fn match_guard_sees_pattern_bindings() {
+        let file = "func foo(value: int):
+\tvar limit = 10
+\tmatch value:
+\t\t1, 2:
+\t\t\tprint(limit)
+\t\tvar x when x > limit:
+\t\t\tprint(x)";
+        let (st, tree) = test_build_st(file);
+        let node_at = |row, column| {
+            let point = tree_sitter::Point::new(row, column);
+            tree.root_node()
+                .descendant_for_point_range(point, point)
+                .unwrap()
+        };
+        let limit = st.resolve_symbol(node_at(1, 5), file);
+        assert!(limit.is_some());
+        assert_eq!(st.resolve_symbol(node_at(4, 9), file), limit);
+        assert_eq!(st.resolve_symbol(node_at(5, 17), file), limit);
+
+        let binding = st.resolve_symbol(node_at(5, 6), file).unwrap();
+        assert_eq!(st.resolve_symbol(node_at(5, 13), file), Some(binding));
+        assert_eq!(st.resolve_symbol(node_at(6, 9), file), Some(binding));
+        assert_eq!(
+            st.map[&binding.0].vars[binding.1].ttype,
+            Some(SymbolType::Variant(VariantType::Int))
+        );
+    }
+
+    #[test]
+    fn membership_and_logical_operators_are_bool() {
+        let file = "func foo(item, items: Array, flags):
+\tvar found = item in items
+\tvar missing = item not in items
+\tvar both = found and flags
+\tvar any = found || flags";
+        let st = test_build_st(file);
+        assert_var_type(&st, "found", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(&st, "missing", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(&st, "both", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(&st, "any", SymbolType::Variant(VariantType::Bool));
+    }
+
+    #[test]
+    fn type_introspection_functions() {
+        let file = "func foo(v):
+\tvar t = typeof(v)
+\tvar name = type_string(t)";
+        let st = test_build_st(file);
+        assert_var_type(&st, "t", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "name", SymbolType::Variant(VariantType::String));
+    }
+
+    #[test]
+    fn range_is_array_of_ints() {
+        let file = "func foo():
+\tvar xs = range(5)
+\tvar steps = range(10, 0, -2)
+\tfor i in range(1, 3):
+\t\tvar next = i";
+        let st = test_build_st(file);
+        assert_var_type(&st, "xs", SymbolType::Array(VariantType::Int));
+        assert_var_type(&st, "steps", SymbolType::Array(VariantType::Int));
+        let (st, tree) = st;
+        let point = tree_sitter::Point::new(4, 6);
+        let next = tree
+            .root_node()
+            .descendant_for_point_range(point, point)
+            .unwrap();
+        assert_eq!(
+            st.declared_symbol(next, file).unwrap().ttype,
+            Some(SymbolType::Variant(VariantType::Int))
+        );
+    }
+
+    #[test]
+    fn symbol_at_declarations_and_usages() {
+        let file = "var speed = 1.0
+func foo(delta):
+\tvar step = speed * delta
+\treturn step";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        let name_at = |line, character| {
+            st.symbol_at(root, Position::new(line, character), file)
+                .map(|symbol| symbol.name.as_str())
+        };
+        assert_eq!(name_at(0, 5), Some("speed"));
+        assert_eq!(name_at(2, 13), Some("speed"));
+        // right after the identifier
+        assert_eq!(name_at(3, 12), Some("step"));
+        assert_eq!(name_at(2, 6), Some("step"));
+        assert_eq!(name_at(2, 21), Some("delta"));
+        assert_eq!(name_at(1, 1), None);
+        assert_eq!(
+            st.symbol_id_at(root, Position::new(2, 6), file),
+            st.symbol_id_at(root, Position::new(3, 9), file)
+        );
+    }
+
+    #[test]
+    fn parameter_and_local_symbol_kinds() {
+        let file = "extends Node
+func foo(delta):
+\tvar step = delta
+\tadd_child(self)";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        let parameter = st.symbol_at(root, Position::new(1, 10), file).unwrap();
+        assert_eq!(parameter.kind, InlayHintKind::TYPE);
+        assert!(parameter.is_parameter);
+        let local = st.symbol_at(root, Position::new(2, 6), file).unwrap();
+        assert_eq!(local.kind, InlayHintKind::TYPE);
+        assert!(!local.is_parameter);
+        let argument_hint = st
+            .map
+            .values()
+            .flat_map(|scope| &scope.vars)
+            .find(|symbol| symbol.name == "node")
+            .unwrap();
+        assert_eq!(argument_hint.kind, InlayHintKind::PARAMETER);
+        assert!(!argument_hint.is_parameter);
+    }
+
+    #[test]
+    fn identifiers_in_conditions_resolve() {
+        let file = "extends Node
+func foo(limit):
+\tvar count = 0
+\tif count > limit:
+\t\tpass
+\telif is_equal_approx(count, limit):
+\t\tpass
+\twhile count < limit:
+\t\tvar step = count
+\t\tcount += step";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        let name_at = |line, character| {
+            st.symbol_at(root, Position::new(line, character), file)
+                .map(|symbol| symbol.hint_position)
+        };
+        let count = Some(Position::new(2, 10));
+        let limit = Some(Position::new(1, 14));
+        assert_eq!(name_at(3, 5), count);
+        assert_eq!(name_at(3, 13), limit);
+        assert_eq!(name_at(7, 8), count);
+        assert_eq!(name_at(7, 16), limit);
+        assert_eq!(name_at(9, 13), Some(Position::new(8, 10)));
+        let hints = st
+            .map
+            .values()
+            .flat_map(|scope| &scope.vars)
+            .filter(|symbol| symbol.kind == InlayHintKind::PARAMETER)
+            .count();
+        assert_eq!(hints, 2);
+    }
+
+    #[test]
+    fn export_flags_are_ints() {
+        let file = "extends Node
+@export_flags(\"Fire\", \"Water\") var elements
+@export_flags_2d_physics
+var layers
+func foo():
+\tvar mask = elements | layers";
+        let (st, tree) = test_build_st(file);
+        let root = tree.root_node();
+        let int = Some(SymbolType::Variant(VariantType::Int));
+        let type_at = |line, character| {
+            st.symbol_at(root, Position::new(line, character), file)
+                .and_then(|symbol| symbol.ttype.clone())
+        };
+        assert_eq!(type_at(1, 36), int);
+        assert_eq!(type_at(3, 5), int);
+        assert_eq!(type_at(5, 5), int);
+    }
+
+    #[test]
+    fn calls_before_definition_and_recursive_calls() {
+        let file = "func count(n: int) -> int:
+\tvar rest = count(n - 1)
+\tvar speed = get_speed()
+\tvar label = get_label()
+\treturn rest + 1
+func get_speed() -> float:
+\treturn 1.0
+func get_label():
+\treturn \"player\"";
+        let st = test_build_st(file);
+        assert_var_type(&st, "rest", SymbolType::Variant(VariantType::Int));
+        assert_var_type(&st, "speed", SymbolType::Variant(VariantType::Float));
+        assert_var_type(&st, "label", SymbolType::Variant(VariantType::String));
+    }
+
+    #[test]
+    fn node_shorthands_are_nodes() {
+        let file = "extends Node
+func foo():
+\tvar player = %Player
+\tvar bar = %\"Health Bar\"
+\tvar sprite = $Body/Sprite";
+        let st = test_build_st(file);
+        let node = SymbolType::Object("Node".to_string());
+        assert_var_type(&st, "player", node.clone());
+        assert_var_type(&st, "bar", node.clone());
+        assert_var_type(&st, "sprite", node);
+    }
+
+    #[test]
+    fn super_resolves_through_parent_class() {
+        let file = "extends Node
+func get_path():
+\tvar path = super()
+\tvar ready = super.is_node_ready()
+\tvar parent_path = super.get_path()
+\treturn path";
+        let st = test_build_st(file);
+        assert_var_type(&st, "path", SymbolType::Variant(VariantType::Node_path));
+        assert_var_type(&st, "ready", SymbolType::Variant(VariantType::Bool));
+        assert_var_type(
+            &st,
+            "parent_path",
+            SymbolType::Variant(VariantType::Node_path),
+        );
+    }
+
+    #[test]
+    fn only_called_functions_are_inferred_for_ranges() {
+        let mut file = (0..100)
+            .map(|i| format!("func f{i}():\n\treturn {i}\n"))
+            .collect::<String>();
+        file += "func caller():\n\tvar a = f50()\n";
+        let tree = parse_file(&file).unwrap();
+        let mut st = SymbolTable::new(&TEST_TYPEDB);
+        let byte = file.find("var a").unwrap();
+        st.build_table_in_ranges(&tree, &file, vec![byte..byte + 1]);
+        assert_eq!(st.function_returns.keys().collect::<Vec<_>>(), ["f50"]);
+        // every function but the called and the built one
+        assert_eq!(st.pending_functions.len(), 99);
+        assert_eq!(
+            st.symbol_at(tree.root_node(), Position::new(201, 5), &file)
+                .and_then(|symbol| symbol.ttype.clone()),
+            Some(SymbolType::Variant(VariantType::Int))
+        );
+    }
 }
@@ -0,0 +1,71 @@
+//! Fixtures shared by the unit tests of the handlers
+
+use std::sync::LazyLock;
+
+use async_lsp::lsp_types::*;
+
+use crate::{filedb::FileDatabase, typedb::TypeDatabase, utils::position_to_byte};
+
+pub static TEST_TYPEDB: LazyLock<TypeDatabase> =
+    LazyLock::new(|| TypeDatabase::from_file("./assets/type_info.json").unwrap());
+
+/// Path the tested script is opened at
+pub const TEST_PATH: &str = "/test.gd";
+
+pub fn test_uri() -> Url {
+    Url::from_file_path(TEST_PATH).unwrap()
+}
+
+/// Database with the file opened at [`TEST_PATH`]
+pub fn test_filedb(file: &str) -> FileDatabase {
+    let filedb = FileDatabase::default();
+    filedb.file_opened(TEST_PATH, file.to_string()).unwrap();
+    filedb
+}
+
+/// Code action request for the script at [`TEST_PATH`] with an empty context
+pub fn code_action_params(range: Range) -> CodeActionParams {
+    CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: test_uri() },
+        range,
+        context: CodeActionContext::default(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    }
+}
+
+/// Returns the file after the edits the action makes to the script at [`TEST_PATH`],
+/// `None` for commands and actions without edits of the script
+pub fn apply_action(file: &str, action: Option<&CodeActionOrCommand>) -> Option<String> {
+    let Some(CodeActionOrCommand::CodeAction(action)) = action else {
+        return None;
+    };
+    let edit = action.edit.clone()?;
+    let edits = match (edit.changes, edit.document_changes) {
+        (Some(mut changes), _) => changes.remove(&test_uri())?,
+        (None, Some(DocumentChanges::Edits(document_edits))) => document_edits
+            .into_iter()
+            .filter(|document_edit| document_edit.text_document.uri == test_uri())
+            .flat_map(|document_edit| document_edit.edits)
+            .map(|edit| match edit {
+                OneOf::Left(edit) => edit,
+                OneOf::Right(edit) => edit.text_edit,
+            })
+            .collect(),
+        _ => return None,
+    };
+    Some(apply_edits(file, edits))
+}
+
+/// Applies edits whose ranges refer to the original file, starting from the last one
+/// so the earlier ranges stay valid
+pub fn apply_edits(file: &str, mut edits: Vec<TextEdit>) -> String {
+    edits.sort_by_key(|edit| edit.range.start);
+    let mut result = file.to_string();
+    for edit in edits.iter().rev() {
+        let start = position_to_byte(file, edit.range.start);
+        let end = position_to_byte(file, edit.range.end);
+        result.replace_range(start..end, &edit.new_text);
+    }
+    result
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use async_lsp::lsp_types::*;
+use tree_sitter::Node;
+
+use crate::{
+    filedb::FileDatabase,
+    utils::{node_content, node_to_range, position_to_point, uri_to_path},
+};
+
+/// Offers to convert `func name(x): return x` into `var name = func(x): return x` and back,
+/// only functions with a single statement body are converted
+pub fn toggle_lambda_action(
+    params: &CodeActionParams,
+    filedb: &FileDatabase,
+) -> Option<CodeActionOrCommand> {
+    let uri = &params.text_document.uri;
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+
+    let point = position_to_point(params.range.start);
+    let mut statement = source_file
+        .tree
+        .root_node()
+        .descendant_for_point_range(point, point)?;
+    let (title, new_text) = loop {
+        match statement.kind() {
+            "function_definition" => {
+                let name = node_content(&statement.child_by_field_name("name")?, &file);
+                let (signature, body) = split_function(statement, &file)?;
+                break (
+                    "Convert function to lambda",
+                    format!("var {name} = func{signature}: {body}"),
+                );
+            }
+            "variable_statement"
+                if statement
+                    .parent()
+                    .is_some_and(|parent| matches!(parent.kind(), "source" | "class_body")) =>
+            {
+                let lambda = statement.child_by_field_name("value")?;
+                if lambda.kind() != "lambda" {
+                    return None;
+                }
+                let name = node_content(&statement.child_by_field_name("name")?, &file);
+                let (signature, body) = split_function(lambda, &file)?;
+                break (
+                    "Convert lambda to function",
+                    format!("func {name}{signature}: {body}"),
+                );
+            }
+            _ => statement = statement.parent()?,
+        }
+    };
+
+    let edit = TextEdit::new(node_to_range(&statement), new_text);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Returns text from parameters to the colon including return type, and the only
+/// statement of the body, if the body is a single `return` or expression
+fn split_function<'f>(function: Node, file: &'f str) -> Option<(&'f str, &'f str)> {
+    let parameters = function.child_by_field_name("parameters")?;
+    let body = function.child_by_field_name("body")?;
+    let mut cursor = function.walk();
+    let colon = function
+        .children(&mut cursor)
+        .find(|child| child.kind() == ":")?;
+
+    let mut cursor = body.walk();
+    let mut statements = body
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() != "comment");
+    let statement = statements.next()?;
+    if statements.next().is_some()
+        || !matches!(
+            statement.kind(),
+            "return_statement" | "expression_statement"
+        )
+    {
+        return None;
+    }
+    Some((
+        file[parameters.start_byte()..colon.start_byte()].trim_end(),
+        node_content(&statement, file),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::*;
+
+    use crate::test_utils::{apply_action, code_action_params, test_filedb};
+
+    use super::toggle_lambda_action;
+
+    fn toggle(file: &str, position: Position) -> Option<String> {
+        let params = code_action_params(Range::new(position, position));
+        let action = toggle_lambda_action(&params, &test_filedb(file));
+        apply_action(file, action.as_ref())
+    }
+
+    #[test]
+    fn function_to_lambda_and_back() {
+        let function = "func double(x: int) -> int: return x * 2";
+        let lambda = "var double = func(x: int) -> int: return x * 2";
+        assert_eq!(toggle(function, Position::new(0, 6)).unwrap(), lambda);
+        assert_eq!(toggle(lambda, Position::new(0, 5)).unwrap(), function);
+    }
+
+    #[test]
+    fn multiline_body_is_not_converted() {
+        let file = "func foo(x):
+\tprint(x)
+\treturn x";
+        assert_eq!(toggle(file, Position::new(0, 6)), None);
+    }
+}
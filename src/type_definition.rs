@@ -0,0 +1,109 @@
+use async_lsp::lsp_types::*;
+
+use crate::{
+    classdb::ClassDatabase,
+    filedb::FileDatabase,
+    symbol_table::SymbolTable,
+    typedb::{SymbolType, TypeDatabase},
+    utils::{node_content, position_to_point},
+};
+
+/// Finds the script declaring the type of the symbol under the cursor.
+/// Built-in types have no sources so `None` is returned for them.
+pub fn find_type_definition(
+    path: &str,
+    position: Position,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    classdb: &ClassDatabase,
+) -> Option<Location> {
+    let lock = filedb.files.read();
+    let source_file = lock.get(path)?;
+    let file = source_file.content.to_string();
+    let tree = &source_file.tree;
+
+    let point = position_to_point(position);
+    let node = tree.root_node().descendant_for_point_range(point, point)?;
+    if node.kind() != "identifier" && node.kind() != "name" {
+        return None;
+    }
+
+    let ttype = if node.parent().is_some_and(|parent| parent.kind() == "type") {
+        SymbolType::from_str(node_content(&node, &file))
+    } else {
+        let mut st = SymbolTable::new(typedb);
+        st.build_table(tree, &file);
+        if let Some(symbol) = st.declared_symbol(node, &file) {
+            symbol.ttype.clone()?
+        } else {
+            let scope_id = st.scope_of(node);
+            st.get_symbol_type(scope_id, node_content(&node, &file), node.start_byte())?
+                .clone()
+        }
+    };
+
+    let SymbolType::Object(class_name) = ttype else {
+        return None;
+    };
+    let classes = classdb.classes.read();
+    let class = classes.get(&class_name)?;
+    Some(Location {
+        uri: Url::from_file_path(&class.path).ok()?,
+        range: class.range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{Position, Range, Url};
+
+    use crate::{
+        classdb::ClassDatabase, filedb::FileDatabase, test_utils::TEST_TYPEDB, utils::parse_file,
+    };
+
+    use super::find_type_definition;
+
+    #[test]
+    fn variable_of_custom_type() {
+        let player = "class_name Player
+extends CharacterBody3D";
+        let main = "extends Node
+func foo():
+\tvar p: Player
+\tvar v = Vector3.ZERO
+\tprint(p, v)";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/main.gd", main.to_string())
+            .unwrap();
+        let classdb = ClassDatabase::default();
+        let tree = parse_file(player).unwrap();
+        classdb.update_class("/project/player.gd", &tree, player);
+
+        let location = find_type_definition(
+            "/project/main.gd",
+            Position::new(4, 7),
+            &TEST_TYPEDB,
+            &filedb,
+            &classdb,
+        )
+        .unwrap();
+        assert_eq!(
+            location.uri,
+            Url::from_file_path("/project/player.gd").unwrap()
+        );
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(0, 11), Position::new(0, 17))
+        );
+
+        let location = find_type_definition(
+            "/project/main.gd",
+            Position::new(4, 10),
+            &TEST_TYPEDB,
+            &filedb,
+            &classdb,
+        );
+        assert_eq!(location, None);
+    }
+}
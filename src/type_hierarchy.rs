@@ -0,0 +1,148 @@
+use async_lsp::lsp_types::*;
+
+use crate::{
+    classdb::ClassDatabase,
+    filedb::FileDatabase,
+    typedb::{SymbolType, TypeDatabase},
+    utils::{identifier_at, node_content, node_to_range, uri_to_path},
+};
+
+/// Returns hierarchy item for the built-in or workspace class name under the cursor
+pub fn prepare_type_hierarchy(
+    uri: &Url,
+    position: Position,
+    typedb: &TypeDatabase,
+    filedb: &FileDatabase,
+    classdb: &ClassDatabase,
+) -> Option<Vec<TypeHierarchyItem>> {
+    let lock = filedb.files.read();
+    let source_file = lock.get(&uri_to_path(uri))?;
+    let file = source_file.content.to_string();
+
+    let node = identifier_at(source_file.tree.root_node(), position)?;
+    let name = node_content(&node, &file);
+    let is_known_class = typedb.classes.contains_key(&SymbolType::from_str(name))
+        || classdb.classes.read().contains_key(name);
+    if !is_known_class {
+        return None;
+    }
+    Some(vec![make_item(name, uri, node_to_range(&node), classdb)])
+}
+
+/// Returns the direct parent of the class, the client walks further up by itself
+pub fn supertypes(
+    item: &TypeHierarchyItem,
+    typedb: &TypeDatabase,
+    classdb: &ClassDatabase,
+) -> Vec<TypeHierarchyItem> {
+    let script_parent = classdb
+        .classes
+        .read()
+        .get(&item.name)
+        .and_then(|class| class.parent.clone());
+    let parent = script_parent.or_else(|| {
+        typedb
+            .classes
+            .get(&SymbolType::from_str(&item.name))?
+            .parent
+            .clone()
+    });
+    parent
+        .map(|parent| make_item(&parent.to_string(), &item.uri, item.range, classdb))
+        .into_iter()
+        .collect()
+}
+
+/// Returns workspace and built-in classes directly extending the class
+pub fn subtypes(
+    item: &TypeHierarchyItem,
+    typedb: &TypeDatabase,
+    classdb: &ClassDatabase,
+) -> Vec<TypeHierarchyItem> {
+    let target = SymbolType::from_str(&item.name);
+    let mut names = classdb
+        .classes
+        .read()
+        .iter()
+        .filter(|(_, class)| class.parent.as_ref() == Some(&target))
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    names.extend(
+        typedb
+            .classes
+            .iter()
+            .filter(|(_, class)| class.parent.as_ref() == Some(&target))
+            .map(|(class_type, _)| class_type.to_string()),
+    );
+    names.sort();
+    names
+        .iter()
+        .map(|name| make_item(name, &item.uri, item.range, classdb))
+        .collect()
+}
+
+/// Workspace classes point to their `class_name` statement, built-in classes have no sources
+/// so they reuse location of the item the request started from
+fn make_item(
+    name: &str,
+    fallback_uri: &Url,
+    fallback_range: Range,
+    classdb: &ClassDatabase,
+) -> TypeHierarchyItem {
+    let classes = classdb.classes.read();
+    let script = classes
+        .get(name)
+        .and_then(|class| Some((Url::from_file_path(&class.path).ok()?, class.range)));
+    let detail = match &script {
+        Some((uri, _)) => uri.path().to_string(),
+        None => "built-in".to_string(),
+    };
+    let (uri, range) = script.unwrap_or((fallback_uri.clone(), fallback_range));
+    TypeHierarchyItem {
+        name: name.to_string(),
+        kind: SymbolKind::CLASS,
+        tags: None,
+        detail: Some(detail),
+        uri,
+        range,
+        selection_range: range,
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_lsp::lsp_types::{Position, Url};
+
+    use crate::{
+        classdb::ClassDatabase, filedb::FileDatabase, test_utils::TEST_TYPEDB, utils::parse_file,
+    };
+
+    use super::{prepare_type_hierarchy, subtypes, supertypes};
+
+    #[test]
+    fn builtin_and_workspace_hierarchy() {
+        let player = "class_name Player
+extends CharacterBody3D";
+        let filedb = FileDatabase::default();
+        filedb
+            .file_opened("/project/player.gd", player.to_string())
+            .unwrap();
+        let classdb = ClassDatabase::default();
+        classdb.update_class("/project/player.gd", &parse_file(player).unwrap(), player);
+
+        let uri = Url::from_file_path("/project/player.gd").unwrap();
+        let items =
+            prepare_type_hierarchy(&uri, Position::new(1, 10), &TEST_TYPEDB, &filedb, &classdb)
+                .unwrap();
+        assert_eq!(items[0].name, "CharacterBody3D");
+
+        let parents = supertypes(&items[0], &TEST_TYPEDB, &classdb);
+        assert_eq!(parents.len(), 1);
+        assert_eq!(parents[0].name, "PhysicsBody3D");
+
+        let children = subtypes(&items[0], &TEST_TYPEDB, &classdb);
+        let player_item = children.iter().find(|item| item.name == "Player").unwrap();
+        assert_eq!(player_item.uri, uri);
+    }
+}
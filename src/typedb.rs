@@ -1,21 +1,45 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, path::Path, str::FromStr};
 
 use num_enum::{FromPrimitive, IntoPrimitive};
 use serde::{Deserialize, Serialize};
 use strum::{Display, EnumString};
 
+/// Pseudo classes holding functions and constants available everywhere,
+/// @GDScript has language functions like `assert`, `len` and `preload`
+pub const GLOBAL_SCOPES: [&str; 2] = ["@GlobalScope", "@GDScript"];
+
+/// Location of a type database dumped from the project's engine build, relative to the workspace
+pub const WORKSPACE_TYPE_INFO: &str = ".godot/type_info.json";
+
 pub struct TypeDatabase {
     pub classes: HashMap<SymbolType, ClassInfo>,
 }
 
 impl TypeDatabase {
     pub fn from_file(path: &str) -> Option<Self> {
-        let content = std::fs::read_to_string(path).unwrap();
+        let content = std::fs::read_to_string(path).ok()?;
         Self::from_str(&content)
     }
 
+    /// Loads the type database overriding the bundled one, the configured path is relative
+    /// to the workspace root and takes precedence over `.godot/type_info.json`
+    pub fn from_workspace(
+        workspace_root: Option<&Path>,
+        configured_path: Option<&str>,
+    ) -> Option<Self> {
+        let configured = configured_path.map(|path| match workspace_root {
+            Some(root) => root.join(path),
+            None => Path::new(path).to_path_buf(),
+        });
+        let project = workspace_root.map(|root| root.join(WORKSPACE_TYPE_INFO));
+        [configured, project]
+            .into_iter()
+            .flatten()
+            .find_map(|path| Self::from_file(&path.to_string_lossy()))
+    }
+
     pub fn from_str(content: &str) -> Option<Self> {
-        let json: HashMap<String, ClassInfoJson> = serde_json::from_str(content).unwrap();
+        let json: HashMap<String, ClassInfoJson> = serde_json::from_str(content).ok()?;
         let mut db = Self {
             classes: HashMap::default(),
         };
@@ -112,40 +136,89 @@ impl TypeDatabase {
     }
 
     pub fn get_property_type(&self, class: &SymbolType, symbol: &str) -> Option<&SymbolType> {
-        if let Some(class) = self.classes.get(class) {
-            if let Some(prop) = class.properties.get(symbol) {
-                return Some(&prop.ttype);
-            }
-            if let Some(parent_class) = &class.parent {
-                return self.get_property_type(parent_class, symbol);
-            }
-        }
-        None
+        std::iter::once(class)
+            .chain(self.inheritance_chain(class))
+            .find_map(|class| {
+                let prop = self.classes.get(class)?.properties.get(symbol)?;
+                Some(&prop.ttype)
+            })
     }
 
     pub fn get_callable(&self, class: &SymbolType, callable: &str) -> Option<&MethodInfo> {
-        if let Some(class) = self.classes.get(class) {
-            if let Some(prop) = class.methods.get(callable) {
-                return Some(prop);
-            }
-            if let Some(parent_class) = &class.parent {
-                return self.get_callable(parent_class, callable);
-            }
-        }
-        let global_scope_type = SymbolType::Object("@GlobalScope".to_string());
-        if class != &global_scope_type {
-            self.get_callable(&global_scope_type, callable)
-        } else {
-            None
+        self.get_callable_with_owner(class, callable)
+            .map(|(_, method)| method)
+    }
+
+    /// Same as `get_callable` but also returns the class that defines the method,
+    /// which can be one of the ancestors, @GlobalScope or @GDScript
+    pub fn get_callable_with_owner(
+        &self,
+        class: &SymbolType,
+        callable: &str,
+    ) -> Option<(&SymbolType, &MethodInfo)> {
+        let method = std::iter::once(class)
+            .chain(self.inheritance_chain(class))
+            .find_map(|class| {
+                let (class_type, class_info) = self.classes.get_key_value(class)?;
+                Some((class_type, class_info.methods.get(callable)?))
+            });
+        if method.is_some() || GLOBAL_SCOPES.contains(&class.to_string().as_str()) {
+            return method;
         }
+        GLOBAL_SCOPES.iter().find_map(|scope| {
+            let (scope_type, scope_info) = self
+                .classes
+                .get_key_value(&SymbolType::Object(scope.to_string()))?;
+            Some((scope_type, scope_info.methods.get(callable)?))
+        })
     }
 
-    // Get callable return type in specified class or its ancestors or in @GlobalScope
+    // Get callable return type in specified class or its ancestors or in global scopes
     pub fn get_callable_type(&self, class: &SymbolType, callable: &str) -> Option<&SymbolType> {
         self.get_callable(class, callable)
             .map(|method| &method.return_type)
     }
 
+    /// Returns ancestors of the class starting from its direct parent,
+    /// the walk stops at the first repeated class so cyclic parents can't loop forever
+    pub fn inheritance_chain<'s>(&'s self, class: &SymbolType) -> Vec<&'s SymbolType> {
+        let mut chain = Vec::new();
+        let mut cur_class = self.classes.get(class);
+        while let Some(parent) = cur_class.and_then(|class| class.parent.as_ref()) {
+            if parent == class || chain.contains(&parent) {
+                break;
+            }
+            chain.push(parent);
+            cur_class = self.classes.get(parent);
+        }
+        chain
+    }
+
+    /// Checks if a value of `from` type can be stored in a variable of `to` type
+    pub fn is_assignable(&self, from: &SymbolType, to: &SymbolType) -> bool {
+        from == to
+            || to == &SymbolType::Object("Variant".to_string())
+            || matches!(
+                (from, to),
+                (
+                    SymbolType::Variant(VariantType::Int),
+                    SymbolType::Variant(VariantType::Float)
+                )
+            )
+            || self.inheritance_chain(from).contains(&to)
+    }
+
+    /// Returns the type both values can be assigned to, `Variant` if they are incompatible
+    pub fn common_type(&self, a: &SymbolType, b: &SymbolType) -> SymbolType {
+        if self.is_assignable(a, b) {
+            b.clone()
+        } else if self.is_assignable(b, a) {
+            a.clone()
+        } else {
+            SymbolType::Object("Variant".to_string())
+        }
+    }
+
     pub fn get_binary_operator_type(
         &self,
         class: &SymbolType,
@@ -203,6 +276,22 @@ pub struct MethodInfo {
     pub parameters: Vec<MethodParameter>,
 }
 
+impl MethodInfo {
+    /// Formats method as GDScript function declaration, e.g. `func get_child(idx: int) -> Node`
+    pub fn signature(&self, name: &str) -> String {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|param| format!("{}: {}", param.name, param.ttype.to_string()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "func {name}({parameters}) -> {}",
+            self.return_type.to_string()
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct MethodParameter {
     pub name: String,
@@ -276,7 +365,9 @@ pub enum SymbolType {
     Variant(VariantType),
     Array(VariantType),
     Object(String),
-    OjbectArray(String),
+    ObjectArray(String),
+    /// Typed `Dictionary[K, V]`, untyped dictionaries are `Variant(VariantType::Dictionary)`
+    Dictionary(Box<SymbolType>, Box<SymbolType>),
 }
 
 impl SymbolType {
@@ -284,29 +375,82 @@ impl SymbolType {
         match (self, other) {
             (Self::Variant(VariantType::Float), Self::Variant(VariantType::Int))
             | (Self::Variant(VariantType::Int), Self::Variant(VariantType::Float)) => true,
+            // string literals are implicitly converted to `StringName` arguments
+            (Self::Variant(VariantType::String), Self::Variant(VariantType::String_name))
+            | (Self::Variant(VariantType::String_name), Self::Variant(VariantType::String)) => true,
             _ => self == other,
         }
     }
+
+    /// Typed containers share members with their untyped class
+    pub fn untyped(&self) -> SymbolType {
+        match self {
+            Self::Array(_) | Self::ObjectArray(_) => Self::Variant(VariantType::Array),
+            Self::Dictionary(_, _) => Self::Variant(VariantType::Dictionary),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns type of the value produced by indexing `container[key]`
+    pub fn element_type(&self) -> Option<SymbolType> {
+        match self {
+            Self::Array(variant_type) => Some(Self::Variant(*variant_type)),
+            Self::ObjectArray(class_name) => Some(Self::Object(class_name.clone())),
+            Self::Dictionary(_, value_type) => Some(value_type.as_ref().clone()),
+            Self::Variant(VariantType::String) => Some(Self::Variant(VariantType::String)),
+            Self::Variant(variant_type) => variant_type.packed_element_type().map(Self::Variant),
+            _ => None,
+        }
+    }
+
+    /// Type of the loop variable in `for element in value`, dictionaries are iterated by keys
+    pub fn iterated_type(&self) -> Option<SymbolType> {
+        match self {
+            Self::Dictionary(key_type, _) => Some(key_type.as_ref().clone()),
+            Self::Variant(VariantType::Int) => Some(Self::Variant(VariantType::Int)),
+            _ => self.element_type(),
+        }
+    }
 }
 
 impl ToString for SymbolType {
     fn to_string(&self) -> String {
         match self {
             Self::Variant(variant_type) => variant_type.to_string(),
-            Self::Array(variant_type) => format!("{}[]", variant_type),
+            Self::Array(variant_type) => format!("Array[{}]", variant_type),
             Self::Object(name) => name.clone(),
-            Self::OjbectArray(el_name) => format!("{}[]", el_name),
+            Self::ObjectArray(el_name) => format!("Array[{}]", el_name),
+            Self::Dictionary(key_type, value_type) => format!(
+                "Dictionary[{}, {}]",
+                key_type.to_string(),
+                value_type.to_string()
+            ),
         }
     }
 }
 
 impl SymbolType {
+    /// Parses both the `T[]` form used by the type info file and GDScript's `Array[T]`
     pub fn from_str(s: &str) -> Self {
-        if let Some(array_element_type) = s.strip_suffix("[]") {
+        let dictionary_types = s
+            .strip_prefix("Dictionary[")
+            .and_then(|types| types.strip_suffix(']'))
+            .and_then(|types| types.split_once(','));
+        if let Some((key_type, value_type)) = dictionary_types {
+            return Self::Dictionary(
+                Box::new(Self::from_str(key_type.trim())),
+                Box::new(Self::from_str(value_type.trim())),
+            );
+        }
+        let array_element_type = s.strip_suffix("[]").or_else(|| {
+            s.strip_prefix("Array[")
+                .and_then(|element_type| element_type.strip_suffix(']'))
+        });
+        if let Some(array_element_type) = array_element_type {
             match VariantType::from_str(array_element_type) {
                 Ok(v) => Self::Array(v),
                 Err(strum::ParseError::VariantNotFound) => {
-                    Self::OjbectArray(array_element_type.to_string())
+                    Self::ObjectArray(array_element_type.to_string())
                 }
             }
         } else {
@@ -346,14 +490,18 @@ pub enum VariantType {
     Vector4i = 13,
     Plane = 14,
     Quaternion = 15,
+    #[strum(serialize = "AABB")]
     Aabb = 16,
     Basis = 17,
     #[strum(serialize = "Transform3D")]
     Transform3d = 18,
     Projection = 19,
     Color = 20,
+    #[strum(serialize = "StringName")]
     String_name = 21,
+    #[strum(serialize = "NodePath")]
     Node_path = 22,
+    #[strum(serialize = "RID")]
     Rid = 23,
     Object = 24,
     Callable = 25,
@@ -372,6 +520,24 @@ pub enum VariantType {
     PackedVector4Array = 38,
 }
 
+impl VariantType {
+    /// Returns type of the elements of a packed array, `None` for other types
+    pub fn packed_element_type(self) -> Option<VariantType> {
+        match self {
+            Self::PackedByteArray | Self::PackedInt32Array | Self::PackedInt64Array => {
+                Some(Self::Int)
+            }
+            Self::PackedFloat32Array | Self::PackedFloat64Array => Some(Self::Float),
+            Self::PackedStringArray => Some(Self::String),
+            Self::PackedVector2Array => Some(Self::Vector2),
+            Self::PackedVector3Array => Some(Self::Vector3),
+            Self::PackedColorArray => Some(Self::Color),
+            Self::PackedVector4Array => Some(Self::Vector4),
+            _ => None,
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for VariantType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -438,14 +604,14 @@ impl<'de> Deserialize<'de> for PropertyUsage {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::LazyLock;
+    use num_enum::FromPrimitive;
 
-    use crate::typedb::{SymbolType, VariantType};
+    use crate::{
+        test_utils::TEST_TYPEDB,
+        typedb::{SymbolType, VariantType},
+    };
 
-    use super::TypeDatabase;
-
-    static TEST_TYPEDB: LazyLock<TypeDatabase> =
-        LazyLock::new(|| TypeDatabase::from_file("./assets/type_info.json").unwrap());
+    use super::{TypeDatabase, WORKSPACE_TYPE_INFO};
 
     #[test]
     fn read_type_info_file() {
@@ -474,4 +640,125 @@ mod tests {
             SymbolType::Variant(VariantType::Transform2d)
         );
     }
+
+    #[test]
+    fn inherited_callable_reports_defining_class() {
+        let body = SymbolType::Object("CharacterBody3D".to_string());
+        let (owner, method) = TEST_TYPEDB
+            .get_callable_with_owner(&body, "get_tree")
+            .unwrap();
+        assert_eq!(owner, &SymbolType::Object("Node".to_string()));
+        assert_eq!(
+            method.return_type,
+            SymbolType::Object("SceneTree".to_string())
+        );
+
+        let (owner, _) = TEST_TYPEDB.get_callable_with_owner(&body, "max").unwrap();
+        assert_eq!(owner, &SymbolType::Object("@GlobalScope".to_string()));
+    }
+
+    #[test]
+    fn numeric_widening_and_ancestors_are_assignable() {
+        let int = SymbolType::Variant(VariantType::Int);
+        let float = SymbolType::Variant(VariantType::Float);
+        let string = SymbolType::Variant(VariantType::String);
+        assert!(TEST_TYPEDB.is_assignable(&int, &float));
+        assert!(!TEST_TYPEDB.is_assignable(&float, &int));
+        assert_eq!(TEST_TYPEDB.common_type(&int, &float), float);
+        assert_eq!(
+            TEST_TYPEDB.common_type(&int, &string),
+            SymbolType::Object("Variant".to_string())
+        );
+        assert!(TEST_TYPEDB.is_assignable(
+            &SymbolType::Object("CharacterBody3D".to_string()),
+            &SymbolType::Object("Node".to_string())
+        ));
+    }
+
+    #[test]
+    fn cyclic_parents_terminate() {
+        let class = |name: &str, parent: &str| {
+            format!(
+                r#""{name}": {{"name": "{name}", "parent": "{parent}", "methods": [], "properties": [],
+                "constructors": [], "constants": [], "binary_operators": [], "unary_operators": []}}"#
+            )
+        };
+        let json = format!("{{{}, {}}}", class("A", "B"), class("B", "A"));
+        let typedb = TypeDatabase::from_str(&json).unwrap();
+        let a = SymbolType::Object("A".to_string());
+        assert_eq!(
+            typedb.inheritance_chain(&a),
+            vec![&SymbolType::Object("B".to_string())]
+        );
+        assert_eq!(typedb.get_property_type(&a, "missing"), None);
+        assert!(typedb.get_callable(&a, "missing").is_none());
+        assert!(!typedb.is_assignable(&a, &SymbolType::Variant(VariantType::Int)));
+    }
+
+    #[test]
+    fn gdscript_functions_are_declared() {
+        let node = SymbolType::Object("Node".to_string());
+        for function in ["assert", "print_debug", "len", "preload", "range"] {
+            let (owner, _) = TEST_TYPEDB
+                .get_callable_with_owner(&node, function)
+                .unwrap();
+            assert_eq!(owner, &SymbolType::Object("@GDScript".to_string()));
+        }
+        assert_eq!(
+            TEST_TYPEDB.get_callable_type(&node, "len"),
+            Some(&SymbolType::Variant(VariantType::Int))
+        );
+        assert!(TEST_TYPEDB.get_callable(&node, "not_a_function").is_none());
+    }
+
+    #[test]
+    fn workspace_type_info_overrides_bundled() {
+        let root = std::env::temp_dir().join(format!("sidekick-typedb-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(".godot")).unwrap();
+        let alternate = r#"{"Node": {"name": "Node", "parent": null, "methods": [], "properties": [], "constants": [], "constructors": [], "binary_operators": [], "unary_operators": []}}"#;
+        std::fs::write(root.join(WORKSPACE_TYPE_INFO), alternate).unwrap();
+        std::fs::copy("./assets/type_info.json", root.join("engine.json")).unwrap();
+
+        let project = TypeDatabase::from_workspace(Some(&root), None).unwrap();
+        let configured = TypeDatabase::from_workspace(Some(&root), Some("engine.json")).unwrap();
+        let missing = TypeDatabase::from_workspace(Some(&root), Some("missing.json"));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(project.classes.len(), 1);
+        assert_eq!(configured.classes.len(), TEST_TYPEDB.classes.len());
+        // a missing configured file falls back to the project one
+        assert_eq!(missing.unwrap().classes.len(), 1);
+        assert!(TypeDatabase::from_workspace(None, None).is_none());
+    }
+
+    #[test]
+    fn type_names_round_trip() {
+        let mut types = Vec::new();
+        for primitive in 0..=38 {
+            let variant_type = VariantType::from_primitive(primitive);
+            types.push(SymbolType::Variant(variant_type));
+            types.push(SymbolType::Array(variant_type));
+            types.push(SymbolType::Dictionary(
+                Box::new(SymbolType::Variant(variant_type)),
+                Box::new(SymbolType::Array(variant_type)),
+            ));
+        }
+        for class_name in ["Node", "CharacterBody3D", "Variant", "Player.State"] {
+            types.push(SymbolType::Object(class_name.to_string()));
+            types.push(SymbolType::ObjectArray(class_name.to_string()));
+            types.push(SymbolType::Dictionary(
+                Box::new(SymbolType::Variant(VariantType::String_name)),
+                Box::new(SymbolType::ObjectArray(class_name.to_string())),
+            ));
+        }
+        types.extend(TEST_TYPEDB.classes.keys().cloned());
+        for ttype in types {
+            assert_eq!(SymbolType::from_str(&ttype.to_string()), ttype);
+        }
+        assert_eq!(SymbolType::Variant(VariantType::Aabb).to_string(), "AABB");
+        assert_eq!(
+            SymbolType::from_str("Node[]"),
+            SymbolType::ObjectArray("Node".to_string())
+        );
+    }
 }
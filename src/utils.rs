@@ -1,5 +1,10 @@
-use async_lsp::lsp_types::{Position, Range};
-use tree_sitter::{Node, Point, Tree};
+use std::fmt;
+
+use async_lsp::lsp_types::{Position, Range, Url};
+use ropey::{LineType, Rope};
+use tree_sitter::{LanguageError, Node, Point, Tree};
+
+use crate::perf::PERF_TARGET;
 
 pub const fn position_to_point(position: Position) -> Point {
     Point::new(position.line as usize, position.character as usize)
@@ -19,22 +24,284 @@ pub fn node_to_range(node: &Node) -> Range {
     )
 }
 
-pub fn parse_file(content: &str) -> Option<Tree> {
+/// Converts position to byte offset in the content, character counts UTF-16 code units
+/// as LSP positions do and is clamped to the end of the line
+pub fn position_to_byte(content: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match content[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return content.len(),
+        }
+    }
+    let line_end = content[line_start..]
+        .find('\n')
+        .map_or(content.len(), |i| line_start + i);
+    line_start + utf16_to_byte_column(&content[line_start..line_end], position.character)
+}
+
+/// Returns byte offset of the UTF-16 `character` in the line, an offset inside
+/// a character moves to its end
+pub fn utf16_to_byte_column(line: &str, character: u32) -> usize {
+    let mut utf16 = 0;
+    for (index, ch) in line.char_indices() {
+        if utf16 >= character as usize {
+            return index;
+        }
+        utf16 += ch.len_utf16();
+    }
+    line.len()
+}
+
+/// Returns number of UTF-16 code units in the line before the byte column
+pub fn byte_to_utf16_column(line: &str, column: usize) -> u32 {
+    line.char_indices()
+        .take_while(|(index, _)| *index < column)
+        .map(|(_, ch)| ch.len_utf16() as u32)
+        .sum()
+}
+
+/// Converts tree-sitter point, whose column is in bytes, to LSP position in UTF-16 code units
+pub fn point_to_utf16_position(content: &str, point: Point) -> Position {
+    let line = content.split('\n').nth(point.row).unwrap_or("");
+    Position::new(point.row as u32, byte_to_utf16_column(line, point.column))
+}
+
+/// Same as [`node_to_range`] with columns converted to UTF-16 code units
+pub fn node_to_utf16_range(node: &Node, content: &str) -> Range {
+    Range::new(
+        point_to_utf16_position(content, node.start_position()),
+        point_to_utf16_position(content, node.end_position()),
+    )
+}
+
+/// Moves positions sent by the client into the document bounds, a line past the end
+/// is clamped to the end of the last line and a character past the end to the line end,
+/// characters count UTF-16 code units
+pub fn clamp_range(content: &Rope, range: Range) -> Range {
+    let start = clamp_position(content, range.start);
+    let end = clamp_position(content, range.end);
+    Range::new(start, end.max(start))
+}
+
+fn clamp_position(content: &Rope, position: Position) -> Position {
+    let last_line = content.len_lines(LineType::LF_CR) - 1;
+    let line = (position.line as usize).min(last_line);
+    let line_content = content.line(line, LineType::LF_CR).to_string();
+    let line_content = line_content.trim_end_matches(['\n', '\r']);
+    let line_len = byte_to_utf16_column(line_content, line_content.len());
+    let character = if position.line as usize > last_line {
+        line_len
+    } else {
+        position.character.min(line_len)
+    };
+    Position::new(line as u32, character)
+}
+
+/// Converts document URI to the OS path files are keyed and read by
+pub fn uri_to_path(uri: &Url) -> String {
+    uri.to_file_path().map_or_else(
+        |_| uri.path().to_string(),
+        |path| path.to_string_lossy().to_string(),
+    )
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    /// Grammar is incompatible with the linked tree-sitter version
+    Language(LanguageError),
+    /// Parser returned no tree, e.g. because parsing was cancelled
+    NoTree,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language(error) => write!(f, "failed to load GDScript grammar: {error}"),
+            Self::NoTree => write!(f, "parser didn't produce a syntax tree"),
+        }
+    }
+}
+
+pub fn try_parse_file(content: &str) -> Result<Tree, ParseError> {
+    let _span = tracing::debug_span!(target: PERF_TARGET, "parse").entered();
     let mut parser = tree_sitter::Parser::new();
     parser
         .set_language(&tree_sitter_gdscript::LANGUAGE.into())
-        .unwrap();
+        .map_err(ParseError::Language)?;
+
+    parser.parse(content, None).ok_or(ParseError::NoTree)
+}
 
-    parser.parse(content, None)
+pub fn parse_file(content: &str) -> Option<Tree> {
+    try_parse_file(content).ok()
 }
 
-pub fn reparse_file(content: &str, old_tree: &Tree) -> Option<Tree> {
+/// Reparses the edited file reading the rope chunk by chunk,
+/// so the content isn't copied into a string on every keystroke
+pub fn reparse_file(content: &Rope, old_tree: &Tree) -> Option<Tree> {
+    let _span = tracing::debug_span!(target: PERF_TARGET, "reparse").entered();
     let mut parser = tree_sitter::Parser::new();
     parser
         .set_language(&tree_sitter_gdscript::LANGUAGE.into())
         .unwrap();
 
-    parser.parse(content, Some(old_tree))
+    parser.parse_with_options(
+        &mut |byte, _| {
+            if byte >= content.len() {
+                return &[][..];
+            }
+            let (chunk, chunk_start) = content.chunk(byte);
+            &chunk.as_bytes()[byte - chunk_start..]
+        },
+        Some(old_tree),
+        None,
+    )
+}
+
+/// Returns identifier under or right before the cursor
+pub fn identifier_at(root: Node, position: Position) -> Option<Node> {
+    let is_identifier = |node: &Node| matches!(node.kind(), "identifier" | "name");
+    let point = position_to_point(position);
+    let node = root.descendant_for_point_range(point, point)?;
+    if is_identifier(&node) {
+        return Some(node);
+    }
+    let point = Point::new(point.row, point.column.checked_sub(1)?);
+    root.descendant_for_point_range(point, point)
+        .filter(is_identifier)
+}
+
+/// Calls the callback for the node and all of its descendants
+pub fn walk_tree<'t>(node: Node<'t>, callback: &mut impl FnMut(Node<'t>)) {
+    callback(node);
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        walk_tree(child, callback);
+    }
+}
+
+/// Returns text of the `##` comment block right above the declaration,
+/// annotations between the comment and the declaration are skipped
+pub fn doc_comment(declaration: Node, file: &str) -> Option<String> {
+    let mut doc_lines = Vec::new();
+    let preceding_lines = file
+        .lines()
+        .take(declaration.start_position().row)
+        .collect::<Vec<_>>();
+    for line in preceding_lines.iter().rev().map(|line| line.trim()) {
+        if let Some(text) = line.strip_prefix("##") {
+            doc_lines.push(text.strip_prefix(' ').unwrap_or(text));
+        } else if !(doc_lines.is_empty() && line.starts_with('@')) {
+            break;
+        }
+    }
+    if doc_lines.is_empty() {
+        return None;
+    }
+    doc_lines.reverse();
+    Some(doc_lines.join("\n"))
+}
+
+/// Returns declaration line of the function without the trailing colon, e.g. `func foo(a: int) -> int`
+pub fn function_header<'s>(function: Node, file: &'s str) -> &'s str {
+    let end = function
+        .child_by_field_name("body")
+        .map_or(function.end_byte(), |body| body.start_byte());
+    file[function.start_byte()..end]
+        .trim_end()
+        .trim_end_matches(':')
+}
+
+/// Returns name of the annotation without `@`, e.g. `export_range`
+pub fn annotation_name<'f>(annotation: Node, file: &'f str) -> &'f str {
+    let mut cursor = annotation.walk();
+    annotation
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "identifier")
+        .map_or("", |name| node_content(&name, file))
+}
+
+/// Annotations written on the same line as the declaration or on the lines before it
+pub fn declaration_annotations<'t>(declaration: Node<'t>) -> Vec<Node<'t>> {
+    let mut annotations = Vec::new();
+    let mut collect = |node: Node<'t>| {
+        let mut cursor = node.walk();
+        match node.kind() {
+            "annotation" => annotations.push(node),
+            "annotations" => annotations.extend(
+                node.named_children(&mut cursor)
+                    .filter(|child| child.kind() == "annotation"),
+            ),
+            _ => (),
+        }
+    };
+    let mut cursor = declaration.walk();
+    for child in declaration.children(&mut cursor) {
+        collect(child);
+    }
+    let mut sibling = declaration.prev_named_sibling();
+    while let Some(node) = sibling
+        && matches!(node.kind(), "annotation" | "annotations")
+    {
+        collect(node);
+        sibling = node.prev_named_sibling();
+    }
+    annotations
+}
+
+/// Returns source text of the annotation arguments, strings keep their quotes
+pub fn annotation_arguments<'f>(annotation: Node, file: &'f str) -> Vec<&'f str> {
+    let mut cursor = annotation.walk();
+    let Some(arguments) = annotation
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "arguments")
+    else {
+        return Vec::new();
+    };
+    let mut cursor = arguments.walk();
+    arguments
+        .named_children(&mut cursor)
+        .map(|argument| node_content(&argument, file))
+        .collect()
+}
+
+/// Strips code fences and inline code marks for clients rendering only plain text,
+/// contents of fenced blocks are kept as is
+pub fn markdown_to_plain_text(markdown: &str) -> String {
+    let mut in_fence = false;
+    let mut lines = Vec::new();
+    for line in markdown.lines() {
+        if line.starts_with("```") {
+            in_fence = !in_fence;
+        } else if in_fence {
+            lines.push(line.to_string());
+        } else {
+            lines.push(strip_inline_code(line));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Removes backtick runs that open and close inline code, unmatched backticks are text
+fn strip_inline_code(line: &str) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        let ticks = rest[start..].len() - rest[start..].trim_start_matches('`').len();
+        let delimiter = &rest[start..start + ticks];
+        let after = &rest[start + ticks..];
+        if let Some(end) = after.find(delimiter) {
+            result.push_str(&rest[..start]);
+            result.push_str(&after[..end]);
+            rest = &after[end + ticks..];
+        } else {
+            result.push_str(&rest[..start + ticks]);
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    result
 }
 
 pub fn node_content<'s>(node: &Node, file_content: &'s str) -> &'s str {
@@ -46,3 +313,197 @@ pub const fn range_contains(range: Range, position: Position) -> bool {
         || (range.start.line == position.line && range.start.character <= position.character)
         || (range.end.line == position.line && range.end.character >= position.character)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        ffi::c_void,
+        path::Path,
+    };
+
+    use async_lsp::lsp_types::{Position, Range, Url};
+    use ropey::Rope;
+    use tree_sitter::{InputEdit, Point, Tree};
+
+    use super::{
+        clamp_range, markdown_to_plain_text, parse_file, point_to_utf16_position, position_to_byte,
+        reparse_file, uri_to_path,
+    };
+
+    thread_local! {
+        /// Number and total size of the allocations made on the thread
+        static ALLOCATIONS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+    }
+
+    fn count_allocation(size: usize) {
+        let _ = ALLOCATIONS.try_with(|allocations| {
+            let (count, bytes) = allocations.get();
+            allocations.set((count + 1, bytes + size));
+        });
+    }
+
+    /// Counts allocations per thread, so parallel tests don't interfere
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_allocation(layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    unsafe extern "C" {
+        fn malloc(size: usize) -> *mut c_void;
+        fn calloc(count: usize, size: usize) -> *mut c_void;
+        fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+        fn free(ptr: *mut c_void);
+    }
+
+    // the parser allocates from C, its allocator is only wrapped,
+    // so memory allocated before the switch can still be freed
+    unsafe extern "C" fn counting_malloc(size: usize) -> *mut c_void {
+        count_allocation(size);
+        unsafe { malloc(size) }
+    }
+
+    unsafe extern "C" fn counting_calloc(items: usize, size: usize) -> *mut c_void {
+        count_allocation(items * size);
+        unsafe { calloc(items, size) }
+    }
+
+    unsafe extern "C" fn counting_realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+        count_allocation(size);
+        unsafe { realloc(ptr, size) }
+    }
+
+    unsafe extern "C" fn counting_free(ptr: *mut c_void) {
+        unsafe { free(ptr) }
+    }
+
+    /// Returns number and total size of the allocations made by the closure
+    fn allocations_during(f: impl FnOnce()) -> (usize, usize) {
+        let (count_before, bytes_before) = ALLOCATIONS.with(Cell::get);
+        f();
+        let (count, bytes) = ALLOCATIONS.with(Cell::get);
+        (count - count_before, bytes - bytes_before)
+    }
+
+    /// Reparse as it was done before reading rope chunks was supported
+    fn reparse_from_string(content: &Rope, old_tree: &Tree) -> Option<Tree> {
+        let mut parser = tree_sitter::Parser::new();
+        parser
+            .set_language(&tree_sitter_gdscript::LANGUAGE.into())
+            .unwrap();
+        parser.parse(content.to_string(), Some(old_tree))
+    }
+
+    #[test]
+    fn path_round_trips_through_uri() {
+        let path = std::env::temp_dir().join("project").join("main.gd");
+        let uri = Url::from_file_path(&path).unwrap();
+        let converted = uri_to_path(&uri);
+        assert_eq!(Path::new(&converted), path);
+        assert_eq!(Url::from_file_path(&converted).unwrap(), uri);
+    }
+
+    #[test]
+    fn positions_count_utf16_code_units() {
+        // `é` is 2 bytes and 1 UTF-16 unit, `😀` is 4 bytes and 2 UTF-16 units
+        let file = "var a = \"é😀\"\nvar b";
+        assert_eq!(position_to_byte(file, Position::new(0, 10)), 11);
+        assert_eq!(position_to_byte(file, Position::new(0, 12)), 15);
+        // inside the surrogate pair, the offset stays on a char boundary
+        assert_eq!(position_to_byte(file, Position::new(0, 11)), 15);
+        assert_eq!(position_to_byte(file, Position::new(0, 100)), 16);
+        assert_eq!(position_to_byte(file, Position::new(1, 4)), 21);
+        assert_eq!(
+            point_to_utf16_position(file, Point::new(0, 15)),
+            Position::new(0, 12)
+        );
+    }
+
+    #[test]
+    fn plain_text_keeps_code_contents() {
+        let markdown = "```gdscript\nvar name = \"`tick`\"\n```\nReturns `name`, a ` is kept";
+        assert_eq!(
+            markdown_to_plain_text(markdown),
+            "var name = \"`tick`\"\nReturns name, a ` is kept"
+        );
+    }
+
+    #[test]
+    fn out_of_range_positions_are_clamped() {
+        // the first line is 13 UTF-16 units long, but 16 bytes and 12 chars
+        let content = Rope::from_str("var a = \"é😀\"\nvar b");
+        let clamp = |start: (u32, u32), end: (u32, u32)| {
+            let range = Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1));
+            let clamped = clamp_range(&content, range);
+            (
+                (clamped.start.line, clamped.start.character),
+                (clamped.end.line, clamped.end.character),
+            )
+        };
+        assert_eq!(clamp((0, 100), (0, 200)), ((0, 13), (0, 13)));
+        assert_eq!(clamp((0, 12), (5, 3)), ((0, 12), (1, 5)));
+        assert_eq!(clamp((1, 4), (0, 2)), ((1, 4), (1, 4)));
+        assert_eq!(clamp((0, 0), (1, 5)), ((0, 0), (1, 5)));
+    }
+
+    #[test]
+    fn reparse_reads_rope_without_copying_it() {
+        unsafe {
+            tree_sitter::set_allocator(
+                Some(counting_malloc),
+                Some(counting_calloc),
+                Some(counting_realloc),
+                Some(counting_free),
+            );
+        }
+        let file = "func foo():\n\tvar a = 10\n\treturn a\n\n".repeat(2000);
+        let mut rope = Rope::from_str(&file);
+        let mut tree = parse_file(&file).unwrap();
+        // `var a = 10` becomes `var a = 100` in the middle of the file
+        let row = 4000 + 1;
+        let column = "\tvar a = 10".len();
+        let byte = file
+            .split_inclusive('\n')
+            .take(row)
+            .map(str::len)
+            .sum::<usize>()
+            + column;
+        rope.insert(byte, "0");
+        tree.edit(&InputEdit {
+            start_byte: byte,
+            old_end_byte: byte,
+            new_end_byte: byte + 1,
+            start_position: Point::new(row, column),
+            old_end_position: Point::new(row, column),
+            new_end_position: Point::new(row, column + 1),
+        });
+
+        let mut reparsed = None;
+        let (rope_count, rope_bytes) = allocations_during(|| reparsed = reparse_file(&rope, &tree));
+        let (string_count, string_bytes) = allocations_during(|| {
+            reparse_from_string(&rope, &tree);
+        });
+        eprintln!(
+            "reparse from rope: {rope_count} allocations of {rope_bytes} bytes, \
+             from string: {string_count} allocations of {string_bytes} bytes"
+        );
+        assert!(rope_bytes < file.len(), "{rope_bytes} bytes allocated");
+        assert!(rope_bytes < string_bytes);
+        assert_eq!(
+            reparsed.unwrap().root_node().to_sexp(),
+            parse_file(&rope.to_string()).unwrap().root_node().to_sexp()
+        );
+    }
+}